@@ -40,7 +40,7 @@ pub fn assemble<'a>(
     let mut diagnostics = Vec::new();
 
     ir.into_iter()
-        .for_each(|ir| match assemble_ir(ir, &symbol_table) {
+        .for_each(|ir| match assemble_ir(ir, symbol_table) {
             Ok(value) => instructions.extend(value),
             Err(value) => diagnostics.push(value),
         });
@@ -59,6 +59,8 @@ fn assemble_ir(
         Ir::Sto(address) => handle_addressable(Operation::Store, address, symbol_table),
         Ir::Set(immediate) => handle_set(immediate, symbol_table),
         Ir::Nop => Ok(handle_nop()),
+        Ir::Trp => Ok(handle_trp()),
+        Ir::Irt => Ok(handle_irt()),
         Ir::Hlt => Ok(handle_hlt()),
     }
 }
@@ -129,6 +131,14 @@ fn handle_nop() -> Vec<Instruction> {
     vec![Instruction::new_with_raw_value(u6::new(0b001100))]
 }
 
+fn handle_trp() -> Vec<Instruction> {
+    vec![Instruction::new_with_raw_value(u6::new(0b001101))]
+}
+
+fn handle_irt() -> Vec<Instruction> {
+    vec![Instruction::new_with_raw_value(u6::new(0b001110))]
+}
+
 fn handle_hlt() -> Vec<Instruction> {
     vec![Instruction::new_with_raw_value(u6::new(0b001111))]
 }
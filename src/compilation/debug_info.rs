@@ -0,0 +1,81 @@
+use arbitrary_int::{u12, u6};
+
+use crate::compilation::{
+    ir::{ConditionalKind, IrRegister},
+    span::Span,
+};
+
+/// Where the code for one source statement was placed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Statement {
+    /// The statement's line, without indentation or the line break.
+    pub span: Span,
+    /// 1-based line number.
+    pub line: usize,
+    pub address: u12,
+    pub length: u12,
+    /// Words placed by `SET`, which are never executed.
+    pub data: bool,
+}
+
+impl Statement {
+    pub fn contains(&self, address: u12) -> bool {
+        (self.address..self.address + self.length).contains(&address)
+    }
+}
+
+/// The computed jump of a `LIH`: it lands on `taken` to jump to the target, or three words later.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Branch {
+    pub line: usize,
+    pub dispatch: u12,
+    pub taken: u12,
+}
+
+/// A value read by a check.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    Register(IrRegister),
+    Constant(u6),
+    /// The word at the address made of two operands, high word first.
+    Memory(Box<Operand>, Box<Operand>),
+}
+
+/// An `ASSERT` or `EXPECT`, evaluated before the instruction at `address` runs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Check {
+    pub span: Span,
+    pub line: usize,
+    pub address: u12,
+    /// The instruction before the check, `None` if it is the first statement with code after it.
+    pub after: Option<u12>,
+    /// A label after the check shares its address, like the one closing an `IF`. Jumps to it
+    /// skip the check, which then only runs when execution falls through from `after`.
+    pub fall_through_only: bool,
+    pub left: Operand,
+    pub kind: ConditionalKind,
+    pub right: Operand,
+}
+
+/// Maps the compiled program back to its source.
+#[derive(Debug, Clone, Default)]
+pub struct DebugInfo {
+    /// Statements that produced code, in address order.
+    pub statements: Vec<Statement>,
+    /// Every `LIH`, including the ones generated for `IF` and `WHILE`.
+    pub branches: Vec<Branch>,
+    /// Every `ASSERT` and `EXPECT`, in address order.
+    pub checks: Vec<Check>,
+}
+
+impl DebugInfo {
+    /// The statement whose code includes `address`.
+    pub fn statement_at(&self, address: u12) -> Option<&Statement> {
+        let index = self
+            .statements
+            .partition_point(|statement| statement.address + statement.length <= address);
+        self.statements
+            .get(index)
+            .filter(|statement| statement.contains(address))
+    }
+}
@@ -1,5 +1,6 @@
-use std::{num::IntErrorKind, path::Path};
+use std::{num::IntErrorKind, path::Path, sync::Arc};
 
+use arbitrary_int::u6;
 use colored::{Color, Colorize};
 use strum::Display as EnumDisplay;
 
@@ -19,7 +20,7 @@ impl Diagnostic {
 
         let (line_number, start_index) = self.get_line_info(raw);
         let line_number = line_number.to_string();
-        let header = format!("{}: {}", self.level.to_string().color(self.level.color()), self.kind.to_string()).bold();
+        let header = format!("{}: {}", self.level.to_string().color(self.level.color()), self.kind).bold();
         let file_path = format!(" {}{} {}", " ".repeat(line_number.len()), "-->".bold().blue(), path.display());
         let prefix = format!(" {} |", " ".repeat(line_number.len())).blue().bold();
         let details = format!("{}{}", format!(" {} | ", line_number).blue().bold(), &raw[self.span.as_range()]);
@@ -29,6 +30,11 @@ impl Diagnostic {
         println!("{header}\n{file_path}\n{prefix}\n{details}\n{highlight}\n{prefix}\n{help}\n");
     }
 
+    /// The 1-based line the diagnostic starts on.
+    pub fn line(&self, raw: &str) -> u16 {
+        self.get_line_info(raw).0
+    }
+
     fn get_line_info(&self, raw: &str) -> (u16, usize) {
         raw.char_indices()
             .take_while(|(index, _)| index < &self.span.start)
@@ -65,6 +71,19 @@ pub enum DiagKind {
     ParseImmediate(IntErrorKind),
     IncompleteCharacter,
     UnsupportedCharacter(char),
+    DivisionByZero,
+    UnterminatedBlock,
+    UnexpectedElse,
+    UnexpectedEnd,
+    NonRelocatable,
+    UnusedLabel(Arc<str>),
+    UnreachableCode,
+    FallIntoData,
+    SelfMov,
+    FailedCheck {
+        left: u6,
+        right: u6,
+    },
 }
 
 impl DiagKind {
@@ -73,6 +92,26 @@ impl DiagKind {
             DiagKind::UnexpectedToken { found, expected } => {
                 format!("Expected `{expected}` found `{found}`")
             }
+            DiagKind::DivisionByZero => "The divisor of this constant is zero".to_string(),
+            DiagKind::UnterminatedBlock => "This block is never closed with `END`".to_string(),
+            DiagKind::UnexpectedElse => "`ELSE` must follow an open `IF` block".to_string(),
+            DiagKind::UnexpectedEnd => {
+                "There is no open `IF` or `WHILE` block to close".to_string()
+            }
+            DiagKind::NonRelocatable => {
+                "Object files can only relocate a plain `label:0` or `label:1`".to_string()
+            }
+            DiagKind::UnusedLabel(label) => format!("The label `{label}` is never referenced"),
+            DiagKind::UnreachableCode => {
+                "This follows a `PC` or `HLT` and no label leads here, so it never runs".to_string()
+            }
+            DiagKind::FallIntoData => {
+                "Execution falls into this `SET` and runs the data as an instruction".to_string()
+            }
+            DiagKind::SelfMov => "Moving a register into itself does nothing".to_string(),
+            DiagKind::FailedCheck { left, right } => {
+                format!("The left side is `{left:0>6b}` and the right side is `{right:0>6b}`")
+            }
             diag => format!("????????: {:#?}", diag),
         }
     }
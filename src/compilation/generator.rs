@@ -7,7 +7,7 @@ use strum::IntoEnumIterator;
 
 use super::{
     diagnostic::{DiagKind, DiagLevel, Diagnostic},
-    ir::{AddressTuple, Conditional, ConditionalKind, Either, Immediate, Ir, IrRegister},
+    ir::{AddressTuple, Carry, Conditional, ConditionalKind, Either, Immediate, Ir, IrRegister},
     span::Span,
 };
 
@@ -41,6 +41,17 @@ fn generate_arithmetic_register_distribution(
     (destination, secondary)
 }
 
+pub fn label_address(label: Arc<str>) -> AddressTuple {
+    offset_label_address(label, u12::new(0), Span::new(0, 0))
+}
+
+fn offset_label_address(label: Arc<str>, offset: u12, span: Span) -> AddressTuple {
+    AddressTuple(
+        Either::Immediate(Immediate::LabelP0(label.clone(), offset, span)),
+        Either::Immediate(Immediate::LabelP1(label, offset, span)),
+    )
+}
+
 pub fn unique_label() -> Arc<str> {
     static COUNTER: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
     let label = format!(
@@ -55,6 +66,17 @@ pub struct IrGenerator {
     ir: Vec<Ir>,
     next_address: u12,
     symbol_table: HashMap<Arc<str>, u12>,
+    /// The last global label; local labels (`.LOOP`) are scoped to it.
+    scope: Option<Arc<str>>,
+    /// How many anonymous labels have been defined in each direction (`LAB +` and `LAB -`).
+    anonymous_forward: usize,
+    anonymous_backward: usize,
+    /// `(dispatch, taken)` for each `LIH` since the last `take_branches`.
+    branches: Vec<(u12, u12)>,
+    /// The address of the last instruction pushed.
+    last_instruction: Option<u12>,
+    /// Where each label was defined, in the order they were defined.
+    label_addresses: Vec<u12>,
 }
 
 impl IrGenerator {
@@ -63,10 +85,55 @@ impl IrGenerator {
             ir: Vec::default(),
             next_address: offset,
             symbol_table: HashMap::default(),
+            scope: None,
+            anonymous_forward: 0,
+            anonymous_backward: 0,
+            branches: Vec::new(),
+            last_instruction: None,
+            label_addresses: Vec::new(),
+        }
+    }
+
+    /// Qualify a local label (`.LOOP`) with the current scope; anything else is returned as is.
+    pub fn scoped(&self, label: &str) -> Arc<str> {
+        match (&self.scope, label.starts_with('.')) {
+            (Some(scope), true) => Arc::from(format!("{scope}{label}")),
+            _ => Arc::from(label),
         }
     }
 
+    /// The name of the anonymous label `distance` definitions away, e.g. `--` is two backward.
+    pub fn anonymous(&self, forward: bool, distance: usize) -> Arc<str> {
+        if forward {
+            Arc::from(format!("+{}", self.anonymous_forward + distance - 1))
+        } else {
+            // A reference before enough definitions is left unresolvable.
+            match self.anonymous_backward.checked_sub(distance) {
+                Some(index) => Arc::from(format!("-{index}")),
+                None => Arc::from("-"),
+            }
+        }
+    }
+
+    pub fn next_address(&self) -> u12 {
+        self.next_address
+    }
+
+    pub fn last_instruction(&self) -> Option<u12> {
+        self.last_instruction
+    }
+
+    pub fn label_addresses(&self) -> &[u12] {
+        &self.label_addresses
+    }
+
+    /// The `LIH` jumps generated since the last call, as `(dispatch, taken)` addresses.
+    pub fn take_branches(&mut self) -> Vec<(u12, u12)> {
+        std::mem::take(&mut self.branches)
+    }
+
     pub fn push(&mut self, value: Ir) -> &mut Self {
+        self.last_instruction = Some(self.next_address);
         self.next_address += value.len();
         self.ir.push(value);
         self
@@ -76,6 +143,70 @@ impl IrGenerator {
         (self.ir, self.symbol_table)
     }
 
+    /// Jump over `N` zeroed words of inline memory and return their labels.
+    fn scratch<const N: usize>(&mut self) -> [Arc<str>; N] {
+        let labels: [Arc<str>; N] = std::array::from_fn(|_| unique_label());
+        let end = unique_label();
+
+        self.pc(label_address(end.clone()));
+        labels.iter().for_each(|label| {
+            self.lab(label.clone(), Span::new(0, 0))
+                .unwrap()
+                .set(Immediate::Constant(u6::new(0)));
+        });
+        self.lab(end, Span::new(0, 0)).unwrap();
+
+        labels
+    }
+
+    /// Toggle the highest bit of `register` using `scratch` as a temporary.
+    fn flip_sign(&mut self, register: IrRegister, scratch: IrRegister) -> &mut Self {
+        let sign_bit = Either::Immediate(Immediate::Constant(u6::new(0b100000)));
+
+        // (register | sign_bit) & !(register & sign_bit)
+        self.mov(scratch, Either::Register(register))
+            .and(scratch, sign_bit.clone())
+            .nor(register, sign_bit)
+            .nor(register, Either::Register(scratch))
+    }
+
+    /// Store each value in its memory cell using only [`MEM_REGISTER`].
+    fn spill(&mut self, values: &[(Either, Arc<str>)]) -> &mut Self {
+        let is_mem = |either: &Either| matches!(either, Either::Register(MEM_REGISTER));
+
+        // Anything already in MEM_REGISTER has to be saved before it is overwritten.
+        values
+            .iter()
+            .filter(|(either, _)| is_mem(either))
+            .for_each(|(_, cell)| {
+                self.sto(label_address(cell.clone()));
+            });
+        values
+            .iter()
+            .filter(|(either, _)| !is_mem(either))
+            .for_each(|(either, cell)| {
+                self.mov(MEM_REGISTER, either.clone())
+                    .sto(label_address(cell.clone()));
+            });
+
+        self
+    }
+
+    /// Load each register from its memory cell; later entries win if a register is repeated.
+    fn unspill(&mut self, values: &[(IrRegister, Arc<str>)]) -> &mut Self {
+        // MEM_REGISTER is needed to load the others, so it goes last.
+        let (mem, others): (Vec<_>, Vec<_>) = values
+            .iter()
+            .partition(|(register, _)| *register == MEM_REGISTER);
+
+        others.into_iter().chain(mem).for_each(|(register, cell)| {
+            self.lod(label_address(cell.clone()))
+                .mov(*register, Either::Register(MEM_REGISTER));
+        });
+
+        self
+    }
+
     // Keywords
 
     // Bitwise Logic
@@ -165,9 +296,9 @@ impl IrGenerator {
             .mov(augend, Either::Register(register))
             .mov(carry, Either::Register(register));
 
-        (0..6).into_iter().for_each(|i| {
+        (0..6).for_each(|i| {
             if i != 0 {
-                self.rol(carry)
+                self.shl(carry)
                     .mov(addend, Either::Register(augend))
                     .mov(augend, Either::Register(carry));
             }
@@ -189,9 +320,9 @@ impl IrGenerator {
         self.mov(subtrahend, either)
             .mov(minuend, Either::Register(register));
 
-        (0..6).into_iter().for_each(|i| {
+        (0..6).for_each(|i| {
             if i != 0 {
-                self.rol(carry).mov(subtrahend, Either::Register(carry));
+                self.shl(carry).mov(subtrahend, Either::Register(carry));
             }
 
             self.nor(
@@ -208,6 +339,283 @@ impl IrGenerator {
         self.mov(register, Either::Register(minuend))
     }
 
+    pub fn adc(&mut self, register: IrRegister, either: Either, carry: Carry) -> &mut Self {
+        self.add_with_carry(register, either, carry, false)
+    }
+
+    pub fn sbc(&mut self, register: IrRegister, either: Either, carry: Carry) -> &mut Self {
+        self.add_with_carry(register, either, carry, true)
+    }
+
+    /// Shared by `ADC` and `SBC`; only the lowest bit of the incoming carry is used.
+    fn add_with_carry(
+        &mut self,
+        register: IrRegister,
+        either: Either,
+        carry: Carry,
+        borrow: bool,
+    ) -> &mut Self {
+        let [first, second, carry_cell, result] = self.scratch();
+        let helper = free_register!(MEM_REGISTER).unwrap();
+
+        let mut values = vec![
+            (Either::Register(register), first.clone()),
+            (either, second.clone()),
+        ];
+        if let Carry::Register(carry_register) = carry {
+            values.push((Either::Register(carry_register), carry_cell.clone()));
+        }
+        self.spill(&values);
+
+        if let Carry::Label(ref label, span) = carry {
+            self.lod(offset_label_address(label.clone(), u12::new(0), span));
+        } else {
+            self.lod(label_address(carry_cell.clone()));
+        }
+        self.and(
+            MEM_REGISTER,
+            Either::Immediate(Immediate::Constant(u6::new(0b000001))),
+        )
+        .sto(label_address(carry_cell.clone()));
+
+        // result = first ± second ± carry
+        let operation = |generator: &mut Self, cell: &Arc<str>| {
+            generator
+                .lod(label_address(cell.clone()))
+                .mov(helper, Either::Register(MEM_REGISTER))
+                .lod(label_address(result.clone()));
+            if borrow {
+                generator.sub(MEM_REGISTER, Either::Register(helper))
+            } else {
+                generator.add(MEM_REGISTER, Either::Register(helper))
+            }
+            .sto(label_address(result.clone()));
+        };
+        self.lod(label_address(first.clone()))
+            .sto(label_address(result.clone()));
+        operation(self, &second);
+        operation(self, &carry_cell);
+
+        self.carry_out(first, second, result.clone(), borrow)
+            .sto(label_address(carry_cell.clone()));
+
+        match carry {
+            Carry::Register(carry_register) => {
+                self.unspill(&[(register, result), (carry_register, carry_cell)])
+            }
+            Carry::Label(label, span) => self
+                .sto(offset_label_address(label, u12::new(0), span))
+                .unspill(&[(register, result)]),
+        }
+    }
+
+    /// Leave the carry (or borrow) out of bit five in [`MEM_REGISTER`] as `0` or `1`.
+    ///
+    /// The carry out of `first + second` is `majority(first, second, !result)`, and the borrow out
+    /// of `first - second` is `majority(!first, second, result)`.
+    fn carry_out(
+        &mut self,
+        first: Arc<str>,
+        second: Arc<str>,
+        result: Arc<str>,
+        borrow: bool,
+    ) -> &mut Self {
+        let either_bit = free_register!(MEM_REGISTER).unwrap();
+        let both_bits = free_register!(either_bit, MEM_REGISTER).unwrap();
+
+        self.lod(label_address(second))
+            .mov(both_bits, Either::Register(MEM_REGISTER))
+            .lod(label_address(first));
+        if borrow {
+            self.not(MEM_REGISTER);
+        }
+        self.mov(either_bit, Either::Register(MEM_REGISTER))
+            .or(either_bit, Either::Register(both_bits))
+            .and(both_bits, Either::Register(MEM_REGISTER))
+            .lod(label_address(result));
+        if !borrow {
+            self.not(MEM_REGISTER);
+        }
+        self.and(either_bit, Either::Register(MEM_REGISTER))
+            .or(both_bits, Either::Register(either_bit))
+            .rol(both_bits)
+            .and(
+                MEM_REGISTER,
+                Either::Immediate(Immediate::Constant(u6::new(0b000001))),
+            )
+    }
+
+    /// Add the 12-bit value at `source` to the one at `destination`; both are little-endian pairs.
+    pub fn add12(
+        &mut self,
+        (destination, destination_span): (Arc<str>, Span),
+        (source, source_span): (Arc<str>, Span),
+    ) -> &mut Self {
+        let [carry] = self.scratch();
+        let helper = free_register!(MEM_REGISTER).unwrap();
+
+        self.zero(MEM_REGISTER).sto(label_address(carry.clone()));
+        (0..2).for_each(|offset| {
+            let destination =
+                offset_label_address(destination.clone(), u12::new(offset), destination_span);
+            self.lod(offset_label_address(
+                source.clone(),
+                u12::new(offset),
+                source_span,
+            ))
+            .mov(helper, Either::Register(MEM_REGISTER))
+            .lod(destination.clone())
+            .adc(
+                MEM_REGISTER,
+                Either::Register(helper),
+                Carry::Label(carry.clone(), Span::new(0, 0)),
+            )
+            .sto(destination);
+        });
+
+        self
+    }
+
+    /// Increment the little-endian 12-bit value at `label`.
+    pub fn inc12(&mut self, (label, span): (Arc<str>, Span)) -> &mut Self {
+        let [carry] = self.scratch();
+
+        self.mov(
+            MEM_REGISTER,
+            Either::Immediate(Immediate::Constant(u6::new(1))),
+        )
+        .sto(label_address(carry.clone()));
+        (0..2).for_each(|offset| {
+            let address = offset_label_address(label.clone(), u12::new(offset), span);
+            self.lod(address.clone())
+                .adc(
+                    MEM_REGISTER,
+                    Either::Immediate(Immediate::Constant(u6::new(0))),
+                    Carry::Label(carry.clone(), Span::new(0, 0)),
+                )
+                .sto(address);
+        });
+
+        self
+    }
+
+    pub fn mul(&mut self, register: IrRegister, either: Either) -> &mut Self {
+        let [product, multiplicand, multiplier] = self.scratch();
+        let mask = free_register!(MEM_REGISTER).unwrap();
+
+        self.spill(&[
+            (Either::Register(register), multiplicand.clone()),
+            (either, multiplier.clone()),
+        ])
+        .zero(MEM_REGISTER)
+        .sto(label_address(product.clone()));
+
+        (0..6).for_each(|i| {
+            // Spread bit i of the multiplier across the whole mask: [0b000000 | 0b111111]
+            self.lod(label_address(multiplier.clone()))
+                .and(
+                    MEM_REGISTER,
+                    Either::Immediate(Immediate::Constant(u6::new(1 << i))),
+                )
+                .zero(mask);
+            (0..6).for_each(|_| {
+                self.or(mask, Either::Register(MEM_REGISTER))
+                    .ror(MEM_REGISTER);
+            });
+
+            self.lod(label_address(multiplicand.clone()))
+                .and(mask, Either::Register(MEM_REGISTER))
+                .lod(label_address(product.clone()))
+                .add(MEM_REGISTER, Either::Register(mask))
+                .sto(label_address(product.clone()));
+
+            if i != 5 {
+                self.lod(label_address(multiplicand.clone()))
+                    .shl(MEM_REGISTER)
+                    .sto(label_address(multiplicand.clone()));
+            }
+        });
+
+        self.lod(label_address(product))
+            .mov(register, Either::Register(MEM_REGISTER))
+    }
+
+    pub fn div(&mut self, register: IrRegister, either: Either) -> &mut Self {
+        let quotient = self.long_division(register, either).0;
+        self.lod(label_address(quotient))
+            .mov(register, Either::Register(MEM_REGISTER))
+    }
+
+    pub fn modulo(&mut self, register: IrRegister, either: Either) -> &mut Self {
+        let remainder = self.long_division(register, either).1;
+        self.lod(label_address(remainder))
+            .mov(register, Either::Register(MEM_REGISTER))
+    }
+
+    /// Restoring division; returns the cells holding the quotient and remainder.
+    ///
+    /// Dividing by zero results in a quotient of `0b111111` and a remainder equal to the dividend.
+    fn long_division(&mut self, register: IrRegister, either: Either) -> (Arc<str>, Arc<str>) {
+        // The dividend is shifted out of the left of this cell as the quotient is shifted in on the right.
+        let [quotient, remainder, divisor] = self.scratch();
+        let helper = free_register!(MEM_REGISTER).unwrap();
+
+        self.spill(&[
+            (Either::Register(register), quotient.clone()),
+            (either, divisor.clone()),
+        ])
+        .zero(MEM_REGISTER)
+        .sto(label_address(remainder.clone()));
+
+        (0..6).for_each(|_| {
+            // remainder = (remainder << 1) | (quotient >> 5)
+            self.lod(label_address(quotient.clone()))
+                .rol(MEM_REGISTER)
+                .mov(helper, Either::Register(MEM_REGISTER))
+                .and(
+                    helper,
+                    Either::Immediate(Immediate::Constant(u6::new(0b000001))),
+                )
+                .lod(label_address(remainder.clone()))
+                .shl(MEM_REGISTER)
+                .or(MEM_REGISTER, Either::Register(helper))
+                .sto(label_address(remainder.clone()));
+
+            self.lod(label_address(quotient.clone()))
+                .shl(MEM_REGISTER)
+                .sto(label_address(quotient.clone()));
+
+            // if remainder >= divisor { remainder -= divisor; quotient |= 1 }
+            let skip = unique_label();
+            self.lod(label_address(divisor.clone()))
+                .mov(helper, Either::Register(MEM_REGISTER))
+                .lod(label_address(remainder.clone()))
+                .lih(
+                    Conditional {
+                        left: Either::Register(MEM_REGISTER),
+                        kind: ConditionalKind::Less,
+                        right: Either::Register(helper),
+                    },
+                    label_address(skip.clone()),
+                )
+                .lod(label_address(divisor.clone()))
+                .mov(helper, Either::Register(MEM_REGISTER))
+                .lod(label_address(remainder.clone()))
+                .sub(MEM_REGISTER, Either::Register(helper))
+                .sto(label_address(remainder.clone()))
+                .lod(label_address(quotient.clone()))
+                .or(
+                    MEM_REGISTER,
+                    Either::Immediate(Immediate::Constant(u6::new(0b000001))),
+                )
+                .sto(label_address(quotient.clone()))
+                .lab(skip, Span::new(0, 0))
+                .unwrap();
+        });
+
+        (quotient, remainder)
+    }
+
     // Memory
     pub fn set(&mut self, immediate: Immediate) -> &mut Self {
         self.push(Ir::Set(immediate))
@@ -247,9 +655,28 @@ impl IrGenerator {
     }
 
     pub fn lab(&mut self, label: Arc<str>, span: Span) -> Result<&mut Self, Diagnostic> {
+        let label = match &*label {
+            "+" => {
+                self.anonymous_forward += 1;
+                self.anonymous(true, 0)
+            }
+            "-" => {
+                self.anonymous_backward += 1;
+                self.anonymous(false, 1)
+            }
+            local if local.starts_with('.') => self.scoped(local),
+            // Generated labels and fully qualified local labels don't open a new scope.
+            other if other.starts_with('#') || other.contains('.') => label,
+            _ => {
+                self.scope = Some(label.clone());
+                label
+            }
+        };
+
         match self.symbol_table.entry(label) {
             Entry::Vacant(entry) => {
                 entry.insert(self.next_address);
+                self.label_addresses.push(self.next_address);
                 Ok(self)
             }
             Entry::Occupied(_) => Err(Diagnostic {
@@ -274,10 +701,17 @@ impl IrGenerator {
             ConditionalKind::GreaterEq
             | ConditionalKind::Less
             | ConditionalKind::LessEq
-            | ConditionalKind::Greater => {
+            | ConditionalKind::Greater
+            | ConditionalKind::SignedGreaterEq
+            | ConditionalKind::SignedLess
+            | ConditionalKind::SignedLessEq
+            | ConditionalKind::SignedGreater => {
                 let (left, right) = if matches!(
                     condition.kind,
-                    ConditionalKind::LessEq | ConditionalKind::Greater
+                    ConditionalKind::LessEq
+                        | ConditionalKind::Greater
+                        | ConditionalKind::SignedLessEq
+                        | ConditionalKind::SignedGreater
                 ) {
                     (condition.right, condition.left)
                 } else {
@@ -300,27 +734,32 @@ impl IrGenerator {
                         free_register
                     }
                 };
-                // The following operation is (helper <= MEM_REGISTER):
-
+                // The following operation leaves a non-zero value in MEM_REGISTER if (helper < MEM_REGISTER):
+                // 1. Clear the bits both values share; they can't change the result.
+                // 2. Smear the highest remaining bit of helper to the right.
+                // 3. Anything left in MEM_REGISTER above that is larger than helper.
                 let other_helper = free_register!(helper, MEM_REGISTER).unwrap();
-                (0..6).into_iter().for_each(|i| {
-                    self.mov(other_helper, Either::Register(MEM_REGISTER));
-
-                    self.nor(MEM_REGISTER, Either::Register(helper))
-                        .nor(MEM_REGISTER, Either::Register(helper));
-
-                    self.nor(helper, Either::Register(other_helper))
-                        .nor(helper, Either::Register(other_helper));
-
-                    // This simulates shifting helper in place:
-                    // 1. rol(MEM_REGISTER) is the same as ror(helper)
-                    // 2. Next we need to mask it like shift would from right to left.
-                    //    This can be done with the inverse of [1, 2, 4, 8, 16, 32] or 2.pow(i)
-                    self.rol(MEM_REGISTER).and(
-                        helper,
-                        Either::Immediate(Immediate::Constant(!u6::new(2_u8.pow(i)))),
-                    );
+
+                // Flipping the sign bit of both values maps two's complement onto unsigned order.
+                if condition.kind.is_signed() {
+                    self.flip_sign(helper, other_helper)
+                        .flip_sign(MEM_REGISTER, other_helper);
+                }
+
+                self.mov(other_helper, Either::Register(MEM_REGISTER))
+                    .nor(MEM_REGISTER, Either::Register(helper))
+                    .nor(MEM_REGISTER, Either::Register(helper))
+                    .nor(helper, Either::Register(other_helper))
+                    .nor(helper, Either::Register(other_helper))
+                    .mov(other_helper, Either::Register(MEM_REGISTER));
+
+                (0..5).for_each(|_| {
+                    self.shr(helper).or(helper, Either::Register(MEM_REGISTER));
                 });
+
+                self.nor(other_helper, Either::Register(helper))
+                    .nor(other_helper, Either::Register(helper))
+                    .mov(MEM_REGISTER, Either::Register(other_helper));
             }
         }
 
@@ -329,16 +768,20 @@ impl IrGenerator {
         let helper = free_register!(MEM_REGISTER).unwrap();
         self.zero(helper);
         // Distribute the value until we have [0b000000 | 0b111111]
-        (0..6).into_iter().for_each(|_| {
+        (0..6).for_each(|_| {
             self.or(helper, Either::Register(MEM_REGISTER))
                 .ror(MEM_REGISTER);
         });
 
         // Flip if we are using one of the negated conditions.
-        if condition.kind == ConditionalKind::NotEq
-            || condition.kind == ConditionalKind::Less
-            || condition.kind == ConditionalKind::Greater
-        {
+        if matches!(
+            condition.kind,
+            ConditionalKind::NotEq
+                | ConditionalKind::Less
+                | ConditionalKind::Greater
+                | ConditionalKind::SignedLess
+                | ConditionalKind::SignedGreater
+        ) {
             self.not(helper);
         }
 
@@ -352,10 +795,19 @@ impl IrGenerator {
         let label = unique_label();
         self.add(
             helper,
-            Either::Immediate(Immediate::LabelP1(label.clone(), Span::new(0, 0))),
-        )
-        .pc(AddressTuple(
-            Either::Immediate(Immediate::LabelP0(label.clone(), Span::new(0, 0))),
+            Either::Immediate(Immediate::LabelP1(
+                label.clone(),
+                u12::new(0),
+                Span::new(0, 0),
+            )),
+        );
+        let dispatch = self.next_address;
+        self.pc(AddressTuple(
+            Either::Immediate(Immediate::LabelP0(
+                label.clone(),
+                u12::new(0),
+                Span::new(0, 0),
+            )),
             Either::Register(helper),
         ));
 
@@ -366,6 +818,7 @@ impl IrGenerator {
         }
 
         // If we added three to this label we will skip the last instruction otherwise we will jump to the target address
+        self.branches.push((dispatch, self.next_address));
         self.lab(label, Span::new(0, 0)).unwrap().pc(address)
     }
 
@@ -374,6 +827,14 @@ impl IrGenerator {
         self.push(Ir::Nop)
     }
 
+    pub fn trp(&mut self) -> &mut Self {
+        self.push(Ir::Trp)
+    }
+
+    pub fn irt(&mut self) -> &mut Self {
+        self.push(Ir::Irt)
+    }
+
     pub fn hlt(&mut self) -> &mut Self {
         self.push(Ir::Hlt)
     }
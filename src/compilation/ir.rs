@@ -16,6 +16,8 @@ pub enum Ir {
     Sto(AddressTuple),
     Set(Immediate),
     Nop,
+    Trp,
+    Irt,
     Hlt,
 }
 
@@ -27,8 +29,7 @@ impl Ir {
                 u12::new(1) + address.len()
             }
             Self::Set(_) => u12::new(1),
-            Self::Nop => u12::new(1),
-            Self::Hlt => u12::new(1),
+            Self::Nop | Self::Trp | Self::Irt | Self::Hlt => u12::new(1),
         }
     }
 }
@@ -54,6 +55,12 @@ impl Either {
     }
 }
 
+#[derive(Debug, Clone)]
+pub enum Carry {
+    Register(IrRegister),
+    Label(Arc<str>, Span),
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EnumIter)]
 pub enum IrRegister {
     A,
@@ -76,20 +83,69 @@ pub enum ConditionalKind {
     GreaterEq,
     Less,
     LessEq,
+    SignedGreater,
+    SignedGreaterEq,
+    SignedLess,
+    SignedLessEq,
+}
+
+impl ConditionalKind {
+    /// The condition that is true exactly when this one is false.
+    pub fn negated(&self) -> Self {
+        match self {
+            Self::Eq => Self::NotEq,
+            Self::NotEq => Self::Eq,
+            Self::Greater => Self::LessEq,
+            Self::GreaterEq => Self::Less,
+            Self::Less => Self::GreaterEq,
+            Self::LessEq => Self::Greater,
+            Self::SignedGreater => Self::SignedLessEq,
+            Self::SignedGreaterEq => Self::SignedLess,
+            Self::SignedLess => Self::SignedGreaterEq,
+            Self::SignedLessEq => Self::SignedGreater,
+        }
+    }
+
+    pub fn is_signed(&self) -> bool {
+        matches!(
+            self,
+            Self::SignedGreater | Self::SignedGreaterEq | Self::SignedLess | Self::SignedLessEq
+        )
+    }
+
+    /// Compare two words, as two's complement numbers for the signed kinds.
+    pub fn holds(&self, left: u6, right: u6) -> bool {
+        let value = |word: u6| match self.is_signed() {
+            true => ((word.value() << 2) as i8 >> 2) as i16,
+            false => word.value() as i16,
+        };
+        let (left, right) = (value(left), value(right));
+        match self {
+            Self::Eq => left == right,
+            Self::NotEq => left != right,
+            Self::Greater | Self::SignedGreater => left > right,
+            Self::GreaterEq | Self::SignedGreaterEq => left >= right,
+            Self::Less | Self::SignedLess => left < right,
+            Self::LessEq | Self::SignedLessEq => left <= right,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum Immediate {
     Constant(u6),
-    LabelP0(Arc<str>, Span),
-    LabelP1(Arc<str>, Span),
+    /// The high word of a label's address plus an offset.
+    LabelP0(Arc<str>, u12, Span),
+    /// The low word of a label's address plus an offset.
+    LabelP1(Arc<str>, u12, Span),
     Not(Box<Immediate>),
     And(Box<Immediate>, Box<Immediate>),
     Or(Box<Immediate>, Box<Immediate>),
     Add(Box<Immediate>, Box<Immediate>),
     Sub(Box<Immediate>, Box<Immediate>),
     Mul(Box<Immediate>, Box<Immediate>),
-    Div(Box<Immediate>, Box<Immediate>),
+    /// The span of the `/`, for dividing by zero.
+    Div(Box<Immediate>, Box<Immediate>, Span),
     Rol(Box<Immediate>, Box<Immediate>),
     Ror(Box<Immediate>, Box<Immediate>),
 }
@@ -107,19 +163,38 @@ fn undefined_label_error(span: Span) -> Diagnostic {
 }
 
 impl Immediate {
+    /// The span of the first label referenced anywhere in this expression.
+    pub fn label_span(&self) -> Option<Span> {
+        match self {
+            Immediate::Constant(_) => None,
+            Immediate::LabelP0(_, _, span) | Immediate::LabelP1(_, _, span) => Some(*span),
+            Immediate::Not(value) => value.label_span(),
+            Immediate::And(first, second)
+            | Immediate::Or(first, second)
+            | Immediate::Add(first, second)
+            | Immediate::Sub(first, second)
+            | Immediate::Mul(first, second)
+            | Immediate::Div(first, second, _)
+            | Immediate::Rol(first, second)
+            | Immediate::Ror(first, second) => first.label_span().or_else(|| second.label_span()),
+        }
+    }
+
     pub fn flatten(&self, symbol_table: &HashMap<Arc<str>, u12>) -> Result<u6, Diagnostic> {
         Ok(match self {
             Immediate::Constant(value) => *value,
-            Immediate::LabelP0(value, span) => u12_to_u6(
-                *symbol_table
+            Immediate::LabelP0(value, offset, span) => u12_to_u6(
+                symbol_table
                     .get(value)
                     .ok_or_else(|| undefined_label_error(*span))?
+                    .wrapping_add(*offset)
                     >> 6,
             ),
-            Immediate::LabelP1(value, span) => u12_to_u6(
-                *symbol_table
+            Immediate::LabelP1(value, offset, span) => u12_to_u6(
+                symbol_table
                     .get(value)
-                    .ok_or_else(|| undefined_label_error(*span))?,
+                    .ok_or_else(|| undefined_label_error(*span))?
+                    .wrapping_add(*offset),
             ),
             Immediate::Not(value) => !value.flatten(symbol_table)?,
             Immediate::And(first, second) => {
@@ -128,18 +203,23 @@ impl Immediate {
             Immediate::Or(first, second) => {
                 first.flatten(symbol_table)? | second.flatten(symbol_table)?
             }
-            Immediate::Add(first, second) => {
-                first.flatten(symbol_table)? + second.flatten(symbol_table)?
-            }
-            Immediate::Sub(first, second) => {
-                first.flatten(symbol_table)? - second.flatten(symbol_table)?
-            }
-            Immediate::Mul(first, second) => {
-                first.flatten(symbol_table)? * second.flatten(symbol_table)?
-            }
-            Immediate::Div(first, second) => {
-                first.flatten(symbol_table)? / second.flatten(symbol_table)?
-            }
+            Immediate::Add(first, second) => first
+                .flatten(symbol_table)?
+                .wrapping_add(second.flatten(symbol_table)?),
+            Immediate::Sub(first, second) => first
+                .flatten(symbol_table)?
+                .wrapping_sub(second.flatten(symbol_table)?),
+            Immediate::Mul(first, second) => first
+                .flatten(symbol_table)?
+                .wrapping_mul(second.flatten(symbol_table)?),
+            Immediate::Div(first, second, span) => first
+                .flatten(symbol_table)?
+                .checked_div(second.flatten(symbol_table)?)
+                .ok_or(Diagnostic {
+                    level: DiagLevel::Fatal,
+                    span: *span,
+                    kind: DiagKind::DivisionByZero,
+                })?,
             Immediate::Rol(first, second) => first
                 .flatten(symbol_table)?
                 .rotate_left(second.flatten(symbol_table)?.into()),
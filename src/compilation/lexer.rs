@@ -8,7 +8,8 @@ use crate::compilation::{
 const EOF_CHAR: char = '\0';
 
 fn is_ident(character: char) -> bool {
-    character.is_alphanumeric() || character == '_'
+    // The dot separates a local label from its scope, e.g. `MAIN.LOOP`
+    character.is_alphanumeric() || character == '_' || character == '.'
 }
 
 #[derive(Debug, Clone)]
@@ -72,6 +73,7 @@ impl<'a> Cursor<'a> {
 
         let kind = match first {
             c if c.is_alphabetic() || c == '_' => self.consume_identifier_or_keyword_or_register(c),
+            '.' if is_ident(self.first()) => self.consume_identifier_or_keyword_or_register('.'),
 
             c @ '0'..='9' => self.consume_number(c),
             '\'' => self.consume_character_literal(),
@@ -241,6 +243,18 @@ mod tests {
         ]);
     }
 
+    #[test]
+    fn test_tokenize_local_labels() {
+        let input = ".LOOP MAIN.LOOP . A";
+        let tokens: Vec<TokenKind> = Cursor::from(input).tokenize().map(|t| t.kind).collect();
+        assert_eq!(tokens, vec![
+            TokenKind::Identifier,
+            TokenKind::Identifier,
+            TokenKind::Unknown,
+            TokenKind::Register(Register::A),
+        ]);
+    }
+
     #[test]
     fn test_tokenize_character_literals() {
         let input = "'a' '\n' 'x";
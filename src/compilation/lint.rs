@@ -0,0 +1,340 @@
+use std::{collections::HashMap, sync::Arc};
+
+use clap::ValueEnum;
+use strum::{Display as EnumDisplay, EnumString};
+
+use crate::compilation::{
+    diagnostic::{DiagKind, DiagLevel, Diagnostic},
+    span::Span,
+    tokens::{Keyword, Token, TokenKind},
+};
+
+/// A warning that can be allowed or denied by name, e.g. `--allow unused_label`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EnumDisplay, EnumString, ValueEnum)]
+#[strum(serialize_all = "snake_case")]
+#[value(rename_all = "snake_case")]
+pub enum Lint {
+    /// A label that is never referenced
+    UnusedLabel,
+    /// A statement after `PC` or `HLT` that no label leads to
+    UnreachableCode,
+    /// `SET` data that the statement before it falls into
+    FallIntoData,
+    /// `MOV` of a register into itself
+    SelfMov,
+}
+
+/// The level each lint is reported at; `None` means allowed, everything is a warning by default.
+#[derive(Debug, Clone, Default)]
+pub struct LintLevels(HashMap<Lint, Option<DiagLevel>>);
+
+impl LintLevels {
+    pub fn new(allow: &[Lint], deny: &[Lint]) -> Self {
+        let mut levels = Self::default();
+        allow.iter().for_each(|lint| levels.set(*lint, None));
+        deny.iter()
+            .for_each(|lint| levels.set(*lint, Some(DiagLevel::Fatal)));
+        levels
+    }
+
+    pub fn set(&mut self, lint: Lint, level: Option<DiagLevel>) {
+        self.0.insert(lint, level);
+    }
+
+    pub fn level(&self, lint: Lint) -> Option<DiagLevel> {
+        self.0
+            .get(&lint)
+            .cloned()
+            .unwrap_or(Some(DiagLevel::Warning))
+    }
+
+    /// Apply a `# LINT: ALLOW UNUSED_LABEL, SELF_MOV` (or `DENY`, `WARN`) comment.
+    fn apply_pragma(&mut self, comment: &str) {
+        let comment = comment.trim_start_matches('#').trim().to_ascii_uppercase();
+        let Some(pragma) = comment.strip_prefix("LINT:") else {
+            return;
+        };
+        let mut words = pragma
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|word| !word.is_empty());
+        let level = match words.next() {
+            Some("ALLOW") => None,
+            Some("WARN") => Some(DiagLevel::Warning),
+            Some("DENY") => Some(DiagLevel::Fatal),
+            _ => return,
+        };
+        words
+            .filter_map(|name| name.to_ascii_lowercase().parse::<Lint>().ok())
+            .for_each(|lint| self.set(lint, level.clone()));
+    }
+}
+
+/// A label definition or reference, with local labels qualified by their scope.
+#[derive(Debug, PartialEq)]
+pub struct LabelUse {
+    pub name: String,
+    pub span: Span,
+    pub definition: bool,
+}
+
+/// Every label identifier, following the parser's rules for scoping local labels.
+pub fn label_uses(tokens: &[Token], source: &str) -> Vec<LabelUse> {
+    let mut labels = Vec::new();
+    let mut scope: Option<&str> = None;
+    let mut after_lab = false;
+
+    for token in tokens {
+        if token.kind == TokenKind::Identifier {
+            let raw = &source[token.span.as_range()];
+            let name = match (scope, raw.starts_with('.')) {
+                (Some(scope), true) => format!("{scope}{raw}"),
+                _ => raw.to_string(),
+            };
+            if after_lab && !raw.contains('.') {
+                scope = Some(raw);
+            }
+            labels.push(LabelUse {
+                name,
+                span: token.span,
+                definition: after_lab,
+            });
+        }
+        after_lab = token.kind == TokenKind::Keyword(Keyword::Lab);
+    }
+    labels
+}
+
+/// Warnings for the uppercased `source` and its tokens, at the levels set by `levels` and by
+/// `# LINT:` comments. A pragma on a line of its own applies to the whole file, one after a
+/// statement only to that line.
+pub fn lint(source: &str, tokens: &[Token], levels: &LintLevels) -> Vec<Diagnostic> {
+    let lines: Vec<(&[Token], Option<&Token>)> = tokens
+        .split(|token| token.kind == TokenKind::NewLine)
+        .map(|tokens| match tokens.split_last() {
+            Some((last, code)) if last.kind == TokenKind::LineComment => (code, Some(last)),
+            _ => (tokens, None),
+        })
+        .collect();
+
+    let mut file_levels = levels.clone();
+    let mut line_levels = HashMap::new();
+    for (number, (code, comment)) in lines.iter().enumerate() {
+        let Some(comment) = comment else { continue };
+        let comment = &source[comment.span.as_range()];
+        if code.is_empty() {
+            file_levels.apply_pragma(comment);
+        } else {
+            line_levels.insert(number, comment);
+        }
+    }
+
+    let mut findings = Vec::new();
+    unused_labels(source, tokens, &mut findings);
+    control_flow(&lines, &mut findings);
+    self_moves(&lines, &mut findings);
+
+    findings.sort_by_key(|(_, span, _)| span.start);
+    findings
+        .into_iter()
+        .filter_map(|(lint, span, kind)| {
+            let number = source[..span.start].matches('\n').count();
+            let level = match line_levels.get(&number) {
+                Some(comment) => {
+                    let mut levels = file_levels.clone();
+                    levels.apply_pragma(comment);
+                    levels.level(lint)
+                }
+                None => file_levels.level(lint),
+            }?;
+            Some(Diagnostic { level, span, kind })
+        })
+        .collect()
+}
+
+type Finding = (Lint, Span, DiagKind);
+
+fn statement_span(code: &[Token]) -> Span {
+    code[0].span.merge(code[code.len() - 1].span)
+}
+
+fn unused_labels(source: &str, tokens: &[Token], findings: &mut Vec<Finding>) {
+    let labels = label_uses(tokens, source);
+    findings.extend(
+        labels
+            .iter()
+            .filter(|label| {
+                label.definition
+                    && !labels
+                        .iter()
+                        .any(|other| !other.definition && other.name == label.name)
+            })
+            .map(|label| {
+                (
+                    Lint::UnusedLabel,
+                    label.span,
+                    DiagKind::UnusedLabel(Arc::from(label.name.as_str())),
+                )
+            }),
+    );
+}
+
+/// Follow execution from the top: a label makes code reachable again and `PC` or `HLT` stops it
+/// from falling through to the next statement.
+fn control_flow(lines: &[(&[Token], Option<&Token>)], findings: &mut Vec<Finding>) {
+    let (mut falls, mut reachable, mut reported, mut in_data) = (true, true, false, false);
+
+    for (code, _) in lines {
+        let Some(Token {
+            kind: TokenKind::Keyword(keyword),
+            ..
+        }) = code.first()
+        else {
+            continue;
+        };
+        match keyword {
+            Keyword::Lab => {
+                reachable = true;
+                reported = false;
+                continue;
+            }
+            // Blocks open and close with generated labels that are jumped to.
+            Keyword::While | Keyword::Else | Keyword::End => {
+                (falls, reachable, reported) = (true, true, false);
+                in_data = false;
+                continue;
+            }
+            // Checks produce no code.
+            Keyword::Assert | Keyword::Expect => continue,
+            Keyword::Set => {
+                if falls && !in_data {
+                    findings.push((
+                        Lint::FallIntoData,
+                        statement_span(code),
+                        DiagKind::FallIntoData,
+                    ));
+                }
+                in_data = true;
+                continue;
+            }
+            _ => in_data = false,
+        }
+
+        if !reachable && !reported {
+            findings.push((
+                Lint::UnreachableCode,
+                statement_span(code),
+                DiagKind::UnreachableCode,
+            ));
+            reported = true;
+        }
+        falls = reachable;
+        if matches!(keyword, Keyword::Pc | Keyword::Hlt) {
+            (falls, reachable) = (false, false);
+        }
+    }
+}
+
+fn self_moves(lines: &[(&[Token], Option<&Token>)], findings: &mut Vec<Finding>) {
+    for (code, _) in lines {
+        let [keyword, left, right] = code else {
+            continue;
+        };
+        if keyword.kind == TokenKind::Keyword(Keyword::Mov) {
+            if let (TokenKind::Register(left), TokenKind::Register(right)) =
+                (&left.kind, &right.kind)
+            {
+                if left == right {
+                    findings.push((Lint::SelfMov, statement_span(code), DiagKind::SelfMov));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compilation::lexer::Cursor;
+
+    fn warnings(source: &str, levels: &LintLevels) -> Vec<(String, DiagLevel)> {
+        let tokens: Vec<_> = Cursor::new(source).tokenize().collect();
+        lint(source, &tokens, levels)
+            .into_iter()
+            .map(|diagnostic| (diagnostic.kind.to_string(), diagnostic.level))
+            .collect()
+    }
+
+    fn kinds(source: &str) -> Vec<String> {
+        warnings(source, &LintLevels::default())
+            .into_iter()
+            .map(|(kind, _)| kind)
+            .collect()
+    }
+
+    #[test]
+    fn test_lint_unused_label() {
+        assert_eq!(
+            kinds("LAB MAIN\nLAB .LOOP\nPC .LOOP\nLAB UNUSED\nHLT"),
+            vec!["unused_label", "unused_label"]
+        );
+        assert_eq!(
+            kinds("LAB A1\nLAB .X\nLAB B1\nLIH [A == 1] A1.X\nPC B1"),
+            vec!["unused_label"]
+        );
+        assert_eq!(kinds("LAB +\nPC -\nLAB -\nPC +"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_lint_unreachable_code() {
+        assert_eq!(
+            kinds("HLT\nNOP\nNOP\nPC HLT_AGAIN\nLAB HLT_AGAIN\nHLT"),
+            vec!["unreachable_code"]
+        );
+        assert_eq!(
+            kinds(
+                "IF [A == 1]\n    HLT\nELSE\n    HLT\nEND\nNOP\nWHILE [A == 1]\n    HLT\nEND\nNOP"
+            ),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn test_lint_fall_into_data() {
+        assert_eq!(
+            kinds("NOP\nSET 1\nSET 2\nHLT\nSET 3"),
+            vec!["fall_into_data"]
+        );
+        assert_eq!(kinds("SET 1"), vec!["fall_into_data"]);
+        assert_eq!(
+            kinds(include_str!("../../examples/fibonacci.dcl")),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn test_lint_self_mov() {
+        assert_eq!(kinds("MOV A A\nMOV B C\nMOV C 1"), vec!["self_mov"]);
+    }
+
+    #[test]
+    fn test_lint_levels() {
+        let source = "MOV A A\nLAB UNUSED";
+        let denied = LintLevels::new(&[Lint::UnusedLabel], &[Lint::SelfMov]);
+        assert_eq!(
+            warnings(source, &denied),
+            vec![("self_mov".to_string(), DiagLevel::Fatal)]
+        );
+
+        let source =
+            "# LINT: DENY UNUSED_LABEL\nMOV A A # LINT: ALLOW SELF_MOV\nLAB UNUSED\nMOV B B";
+        assert_eq!(
+            warnings(source, &denied),
+            vec![
+                ("unused_label".to_string(), DiagLevel::Fatal),
+                ("self_mov".to_string(), DiagLevel::Fatal),
+            ]
+        );
+        let source = "# LINT: ALLOW, SELF_MOV UNUSED_LABEL\nMOV A A\nLAB UNUSED";
+        assert!(warnings(source, &LintLevels::default()).is_empty());
+    }
+}
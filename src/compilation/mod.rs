@@ -8,17 +8,28 @@ use arbitrary_int::{u12, u6};
 
 use super::instruction::Instruction;
 
-use self::{assembler::assemble, ir::Ir, lexer::Cursor, parser::Parser, tokens::Token};
+use self::{
+    assembler::assemble,
+    debug_info::DebugInfo,
+    ir::Ir,
+    lexer::Cursor,
+    object::{is_exported, relocations, Object},
+    parser::Parser,
+    tokens::Token,
+};
 
-mod diagnostic;
+pub mod debug_info;
+pub mod diagnostic;
 mod span;
 
-mod ir;
-mod tokens;
+pub mod ir;
+pub mod tokens;
 
 mod assembler;
 pub mod generator;
 pub mod lexer;
+pub mod lint;
+pub mod object;
 pub mod parser;
 
 pub use diagnostic::{DiagLevel, Diagnostic};
@@ -27,6 +38,7 @@ pub use diagnostic::{DiagLevel, Diagnostic};
 pub struct CompileInfo {
     pub duration: Duration,
     pub symbol_table: HashMap<Arc<str>, u12>,
+    pub debug_info: DebugInfo,
     pub binary: Vec<u6>,
     pub instructions: Vec<Instruction>,
     pub ir: Vec<Ir>,
@@ -37,12 +49,13 @@ pub struct CompileInfo {
 pub fn compile_to_binary(source: &str, offset: u12) -> CompileInfo {
     let start_time = Instant::now();
 
-    let tokens = Cursor::new(&source).tokenize().collect();
+    let tokens = Cursor::new(source).tokenize().collect();
 
-    let parser_result = Parser::new(&source, offset).parse();
-    let (ir, symbol_table, mut diagnostics) = (
+    let parser_result = Parser::new(source, offset).parse();
+    let (ir, symbol_table, debug_info, mut diagnostics) = (
         parser_result.ir,
         parser_result.symbol_table,
+        parser_result.debug_info,
         parser_result.diagnostics,
     );
 
@@ -56,6 +69,7 @@ pub fn compile_to_binary(source: &str, offset: u12) -> CompileInfo {
     CompileInfo {
         duration,
         symbol_table,
+        debug_info,
         binary,
         instructions,
         ir,
@@ -63,3 +77,48 @@ pub fn compile_to_binary(source: &str, offset: u12) -> CompileInfo {
         diagnostics,
     }
 }
+
+pub struct ObjectInfo {
+    pub duration: Duration,
+    pub object: Object,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Compile `source` at address zero, keeping label references as relocations for the linker.
+pub fn compile_to_object(source: &str) -> ObjectInfo {
+    let start_time = Instant::now();
+
+    let parser_result = Parser::new(source, u12::new(0)).parse();
+    let (ir, symbols, mut diagnostics) = (
+        parser_result.ir,
+        parser_result.symbol_table,
+        parser_result.diagnostics,
+    );
+
+    let (relocations, more_diagnostics) = relocations(&ir);
+    diagnostics.extend(more_diagnostics);
+
+    // Labels from other objects are left as zeros until they are patched by the linker. A private
+    // label can't come from another object, so one that is missing here is reported as undefined.
+    let mut symbol_table = symbols.clone();
+    for relocation in relocations.iter().filter(|r| is_exported(&r.symbol)) {
+        symbol_table
+            .entry(relocation.symbol.clone())
+            .or_insert(u12::new(0));
+    }
+
+    let (instructions, more_diagnostics) = assemble(&ir, &symbol_table);
+    diagnostics.extend(more_diagnostics);
+
+    let binary = instructions.iter().map(|i| i.raw_value()).collect();
+
+    ObjectInfo {
+        duration: start_time.elapsed(),
+        object: Object {
+            binary,
+            symbols,
+            relocations,
+        },
+        diagnostics,
+    }
+}
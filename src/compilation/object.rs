@@ -0,0 +1,285 @@
+use std::{collections::HashMap, fmt, str::FromStr, sync::Arc};
+
+use arbitrary_int::{u12, u6};
+
+use crate::compilation::{
+    diagnostic::{DiagKind, DiagLevel, Diagnostic},
+    ir::{AddressTuple, Either, Immediate, Ir},
+};
+
+const HEADER: &str = "DIANAC OBJECT";
+
+/// Which 6-bit half of a 12-bit address a relocation patches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Half {
+    High,
+    Low,
+}
+
+/// A word in an object's binary that holds part of a label's address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Relocation {
+    /// The word to patch, relative to the start of the object.
+    pub address: u12,
+    pub half: Half,
+    pub symbol: Arc<str>,
+    pub offset: u12,
+    /// The word holds the bitwise NOT of the address half, as `AND` and `LIH` emit.
+    pub complement: bool,
+}
+
+/// Code compiled at address zero with every label reference left unresolved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Object {
+    pub binary: Vec<u6>,
+    /// Labels defined in this object, relative to its start.
+    pub symbols: HashMap<Arc<str>, u12>,
+    pub relocations: Vec<Relocation>,
+}
+
+#[derive(Debug)]
+pub enum LinkError {
+    DuplicateSymbol(Arc<str>),
+    UndefinedSymbol(Arc<str>),
+    /// The linked image does not fit in the 4096 word address space.
+    Overflow,
+}
+
+#[derive(Debug)]
+pub enum ObjectError {
+    MissingHeader,
+    Malformed { line: usize },
+    RelocationOutOfBounds(u12),
+}
+
+/// Generated and anonymous labels are private to the object that defines them.
+pub fn is_exported(symbol: &str) -> bool {
+    !symbol.starts_with(['#', '+', '-'])
+}
+
+/// Find every immediate that refers to a label and record where it lands in the binary.
+pub fn relocations(ir: &[Ir]) -> (Vec<Relocation>, Vec<Diagnostic>) {
+    let mut relocations = Vec::new();
+    let mut diagnostics = Vec::new();
+    let mut address = u12::new(0);
+
+    let mut visit = |either: &Either, address: u12| {
+        let Either::Immediate(immediate) = either else {
+            return;
+        };
+        let (mut immediate, mut complement) = (immediate, false);
+        while let Immediate::Not(inner) = immediate {
+            immediate = inner;
+            complement = !complement;
+        }
+
+        let (symbol, offset, half) = match immediate {
+            Immediate::LabelP0(symbol, offset, _) => (symbol, offset, Half::High),
+            Immediate::LabelP1(symbol, offset, _) => (symbol, offset, Half::Low),
+            other => {
+                if let Some(span) = other.label_span() {
+                    diagnostics.push(Diagnostic {
+                        level: DiagLevel::Fatal,
+                        span,
+                        kind: DiagKind::NonRelocatable,
+                    });
+                }
+                return;
+            }
+        };
+
+        relocations.push(Relocation {
+            address,
+            half,
+            symbol: symbol.clone(),
+            offset: *offset,
+            complement,
+        });
+    };
+
+    for ir in ir {
+        match ir {
+            Ir::Nor(_, either) => visit(either, address + u12::new(1)),
+            Ir::Pc(AddressTuple(first, second))
+            | Ir::Lod(AddressTuple(first, second))
+            | Ir::Sto(AddressTuple(first, second)) => {
+                visit(first, address + u12::new(1));
+                visit(second, address + u12::new(1) + first.len());
+            }
+            Ir::Set(immediate) => visit(&Either::Immediate(immediate.clone()), address),
+            Ir::Nop | Ir::Trp | Ir::Irt | Ir::Hlt => {}
+        }
+        address += ir.len();
+    }
+
+    (relocations, diagnostics)
+}
+
+/// Place `objects` one after another starting at `offset` and patch every relocation.
+///
+/// References are resolved against the object's own labels first and then against the labels
+/// exported by every other object.
+pub fn link(objects: &[Object], offset: u12) -> Result<Vec<u6>, LinkError> {
+    let mut bases = Vec::with_capacity(objects.len());
+    let mut next = offset.value() as usize;
+    for object in objects {
+        bases.push(u12::new(next as u16));
+        next += object.binary.len();
+        if next > 4096 {
+            return Err(LinkError::Overflow);
+        }
+    }
+
+    let mut exported: HashMap<Arc<str>, u12> = HashMap::new();
+    for (object, base) in objects.iter().zip(&bases) {
+        for (symbol, address) in object.symbols.iter().filter(|(s, _)| is_exported(s)) {
+            if exported
+                .insert(symbol.clone(), base.wrapping_add(*address))
+                .is_some()
+            {
+                return Err(LinkError::DuplicateSymbol(symbol.clone()));
+            }
+        }
+    }
+
+    let mut image = Vec::with_capacity(next - offset.value() as usize);
+    for (object, base) in objects.iter().zip(&bases) {
+        let start = image.len();
+        image.extend_from_slice(&object.binary);
+
+        for relocation in &object.relocations {
+            let address = match object.symbols.get(&relocation.symbol) {
+                Some(address) => base.wrapping_add(*address),
+                None => *exported
+                    .get(&relocation.symbol)
+                    .ok_or_else(|| LinkError::UndefinedSymbol(relocation.symbol.clone()))?,
+            }
+            .wrapping_add(relocation.offset);
+
+            let word = match relocation.half {
+                Half::High => u6::new((address.value() >> 6) as u8),
+                Half::Low => u6::new((address.value() & 0b111111) as u8),
+            };
+            image[start + relocation.address.value() as usize] =
+                if relocation.complement { !word } else { word };
+        }
+    }
+
+    Ok(image)
+}
+
+impl fmt::Display for Object {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{HEADER}")?;
+
+        let mut symbols: Vec<_> = self.symbols.iter().collect();
+        symbols.sort_by_key(|&(symbol, address)| (*address, symbol.clone()));
+        for (symbol, address) in symbols {
+            writeln!(f, "SYMBOL {symbol} {address}")?;
+        }
+
+        for relocation in &self.relocations {
+            let half = match relocation.half {
+                Half::High => "HIGH",
+                Half::Low => "LOW",
+            };
+            let complement = if relocation.complement { " NOT" } else { "" };
+            writeln!(
+                f,
+                "RELOCATION {} {half} {} {}{complement}",
+                relocation.address, relocation.symbol, relocation.offset
+            )?;
+        }
+
+        for chunk in self.binary.chunks(16) {
+            let words: Vec<_> = chunk.iter().map(|word| format!("{word:0>6b}")).collect();
+            writeln!(f, "BINARY {}", words.join(" "))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Object {
+    type Err = ObjectError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut lines = value.lines().enumerate();
+        if lines.next().map(|(_, line)| line.trim()) != Some(HEADER) {
+            return Err(ObjectError::MissingHeader);
+        }
+
+        let mut object = Object {
+            binary: Vec::new(),
+            symbols: HashMap::new(),
+            relocations: Vec::new(),
+        };
+
+        for (index, line) in lines {
+            let malformed = || ObjectError::Malformed { line: index + 1 };
+            let parse_u12 = |value: Option<&str>| {
+                value
+                    .and_then(|value| value.parse::<u16>().ok())
+                    .filter(|value| *value <= 0xFFF)
+                    .map(u12::new)
+                    .ok_or_else(malformed)
+            };
+
+            let mut fields = line.split_whitespace();
+            match fields.next() {
+                Some("SYMBOL") => {
+                    let symbol = Arc::from(fields.next().ok_or_else(malformed)?);
+                    let address = parse_u12(fields.next())?;
+                    object.symbols.insert(symbol, address);
+                }
+                Some("RELOCATION") => {
+                    let address = parse_u12(fields.next())?;
+                    let half = match fields.next() {
+                        Some("HIGH") => Half::High,
+                        Some("LOW") => Half::Low,
+                        _ => return Err(malformed()),
+                    };
+                    let symbol = Arc::from(fields.next().ok_or_else(malformed)?);
+                    let offset = parse_u12(fields.next())?;
+                    let complement = match fields.next() {
+                        Some("NOT") => true,
+                        None => false,
+                        Some(_) => return Err(malformed()),
+                    };
+                    object.relocations.push(Relocation {
+                        address,
+                        half,
+                        symbol,
+                        offset,
+                        complement,
+                    });
+                }
+                Some("BINARY") => {
+                    for word in fields.by_ref() {
+                        let word = u8::from_str_radix(word, 2)
+                            .ok()
+                            .filter(|word| *word < 64)
+                            .ok_or_else(malformed)?;
+                        object.binary.push(u6::new(word));
+                    }
+                }
+                None => {}
+                Some(_) => return Err(malformed()),
+            }
+
+            if fields.next().is_some() {
+                return Err(malformed());
+            }
+        }
+
+        if let Some(relocation) = object
+            .relocations
+            .iter()
+            .find(|relocation| relocation.address.value() as usize >= object.binary.len())
+        {
+            return Err(ObjectError::RelocationOutOfBounds(relocation.address));
+        }
+
+        Ok(object)
+    }
+}
@@ -5,9 +5,12 @@ use arbitrary_int::{u12, u6};
 use crate::{
     character_encoding::encode_character,
     compilation::{
+        debug_info::{Branch, Check, DebugInfo, Operand, Statement},
         diagnostic::{DiagKind, DiagLevel, Diagnostic},
-        generator::IrGenerator,
-        ir::{AddressTuple, Conditional, ConditionalKind, Either, Immediate, Ir, IrRegister},
+        generator::{label_address, unique_label, IrGenerator},
+        ir::{
+            AddressTuple, Carry, Conditional, ConditionalKind, Either, Immediate, Ir, IrRegister,
+        },
         lexer::Cursor,
         span::Span,
         tokens::{Base, Keyword, Register, Token, TokenKind},
@@ -32,21 +35,75 @@ fn unexpected_token_error(found: Token, expected: &'static str) -> Diagnostic {
 }
 
 pub fn u6_from_str_radix(str: &str, radix: u32) -> Result<u6, IntErrorKind> {
-    u6::try_new(u8::from_str_radix(str, radix).map_err(|e| e.kind().clone())?)
+    u6::try_new(u8::from_str_radix(str, radix).map_err(|e| *e.kind())?)
         .map_err(|_| IntErrorKind::PosOverflow)
 }
 
 pub struct ParseResult {
     pub ir: Vec<Ir>,
     pub symbol_table: HashMap<Arc<str>, u12>,
+    pub debug_info: DebugInfo,
     pub diagnostics: Vec<Diagnostic>,
 }
 
+/// An open `IF` or `WHILE` waiting for its `END`; the span points at the opening keyword.
+#[derive(Debug, Clone)]
+enum Block {
+    If {
+        else_label: Arc<str>,
+        end_label: Option<Arc<str>>,
+        span: Span,
+    },
+    While {
+        start_label: Arc<str>,
+        end_label: Arc<str>,
+        span: Span,
+    },
+}
+
+/// An operand of a check, before its labels are resolved.
+#[derive(Debug, Clone)]
+enum PendingOperand {
+    Either(Either),
+    Memory(AddressTuple),
+}
+
+impl PendingOperand {
+    fn resolve(self, symbol_table: &HashMap<Arc<str>, u12>) -> Result<Operand, Diagnostic> {
+        let resolve_either = |either| match either {
+            Either::Register(register) => Ok(Operand::Register(register)),
+            Either::Immediate(immediate) => immediate.flatten(symbol_table).map(Operand::Constant),
+        };
+        match self {
+            Self::Either(either) => resolve_either(either),
+            Self::Memory(AddressTuple(high, low)) => Ok(Operand::Memory(
+                Box::new(resolve_either(high)?),
+                Box::new(resolve_either(low)?),
+            )),
+        }
+    }
+}
+
+/// An `ASSERT` or `EXPECT` waiting for the symbol table to be complete.
+#[derive(Debug, Clone)]
+struct PendingCheck {
+    span: Span,
+    address: u12,
+    after: Option<u12>,
+    /// How many labels were defined before the check.
+    labels: usize,
+    left: PendingOperand,
+    kind: ConditionalKind,
+    right: PendingOperand,
+}
+
 #[derive(Debug, Clone)]
 pub struct Parser<'a> {
     cursor: Cursor<'a>,
     raw: &'a str,
     ir: IrGenerator,
+    blocks: Vec<Block>,
+    checks: Vec<PendingCheck>,
 }
 
 impl<'a> Parser<'a> {
@@ -55,35 +112,113 @@ impl<'a> Parser<'a> {
             cursor: Cursor::new(raw),
             raw,
             ir: IrGenerator::new(offset),
+            blocks: Vec::new(),
+            checks: Vec::new(),
         }
     }
 
     pub fn parse(mut self) -> ParseResult {
         let mut diagnostics = Vec::new();
+        let mut debug_info = DebugInfo::default();
+        let (mut line, mut counted) = (1, 0);
         while !self.cursor.is_eof() {
+            let (start, address) = (self.offset(), self.ir.next_address());
+            let data = matches!(
+                self.cursor.clone().advance_token(),
+                token_kind!(TokenKind::Keyword(Keyword::Set))
+            );
             self.advance_ir().unwrap_or_else(|e| {
                 diagnostics.push(e);
             });
+
+            let length = self.ir.next_address() - address;
+            if length > u12::new(0) {
+                line += self.raw[counted..start].matches('\n').count();
+                counted = start;
+                debug_info.statements.push(Statement {
+                    span: self.line_span(start),
+                    line,
+                    address,
+                    length,
+                    data,
+                });
+                debug_info
+                    .branches
+                    .extend(
+                        self.ir
+                            .take_branches()
+                            .into_iter()
+                            .map(|(dispatch, taken)| Branch {
+                                line,
+                                dispatch,
+                                taken,
+                            }),
+                    );
+            }
         }
 
+        diagnostics.extend(self.blocks.iter().map(|block| {
+            let (Block::If { span, .. } | Block::While { span, .. }) = block;
+            Diagnostic {
+                level: DiagLevel::Fatal,
+                span: *span,
+                kind: DiagKind::UnterminatedBlock,
+            }
+        }));
+
+        let label_addresses = self.ir.label_addresses().to_vec();
         let (ir, symbol_table) = self.ir.finalize();
+        for check in self.checks {
+            let operands = check
+                .left
+                .resolve(&symbol_table)
+                .and_then(|left| Ok((left, check.right.resolve(&symbol_table)?)));
+            match operands {
+                Ok((left, right)) => debug_info.checks.push(Check {
+                    span: check.span,
+                    line: self.raw[..check.span.start].matches('\n').count() + 1,
+                    address: check.address,
+                    after: check.after,
+                    fall_through_only: label_addresses[check.labels..].contains(&check.address),
+                    left,
+                    kind: check.kind,
+                    right,
+                }),
+                Err(diagnostic) => diagnostics.push(diagnostic),
+            }
+        }
 
         ParseResult {
             ir,
             symbol_table,
+            debug_info,
             diagnostics,
         }
     }
 
+    fn offset(&self) -> usize {
+        self.raw.len() - self.cursor.as_str().len()
+    }
+
+    /// The trimmed line that starts at `start`.
+    fn line_span(&self, start: usize) -> Span {
+        let line = self.raw[start..].split('\n').next().unwrap_or_default();
+        let indent = line.len() - line.trim_start().len();
+        Span::new(start + indent, start + line.trim_end().len())
+    }
+
     pub fn advance_ir(&mut self) -> Result<(), Diagnostic> {
         // TODO move to next line on error
-        let keyword = match self.cursor.advance_token() {
+        let (keyword, keyword_span) = match self.cursor.advance_token() {
             token_kind!(TokenKind::NewLine) => return Ok(()),
             token_kind!(TokenKind::LineComment) => {
                 self.parse_end_of_line(false)?;
                 return Ok(());
             }
-            token_kind!(TokenKind::Keyword(keyword)) => keyword,
+            Token {
+                kind: TokenKind::Keyword(keyword),
+                span,
+            } => (keyword, span),
             token => {
                 return Err(unexpected_token_error(token, "Keyword | Comment | NewLine"));
             }
@@ -153,6 +288,42 @@ impl<'a> Parser<'a> {
                 let either = self.parse_either()?;
                 self.ir.sub(register, either);
             }
+            Keyword::Mul => {
+                let register = self.parse_register()?;
+                let either = self.parse_either()?;
+                self.ir.mul(register, either);
+            }
+            Keyword::Div => {
+                let register = self.parse_register()?;
+                let either = self.parse_either()?;
+                self.ir.div(register, either);
+            }
+            Keyword::Mod => {
+                let register = self.parse_register()?;
+                let either = self.parse_either()?;
+                self.ir.modulo(register, either);
+            }
+            Keyword::Adc => {
+                let register = self.parse_register()?;
+                let either = self.parse_either()?;
+                let carry = self.parse_carry()?;
+                self.ir.adc(register, either, carry);
+            }
+            Keyword::Sbc => {
+                let register = self.parse_register()?;
+                let either = self.parse_either()?;
+                let carry = self.parse_carry()?;
+                self.ir.sbc(register, either, carry);
+            }
+            Keyword::Add12 => {
+                let destination = self.parse_reference()?;
+                let source = self.parse_reference()?;
+                self.ir.add12(destination, source);
+            }
+            Keyword::Inc12 => {
+                let label = self.parse_reference()?;
+                self.ir.inc12(label);
+            }
             // Memory
             Keyword::Set => {
                 let immediate = self.parse_immediate()?;
@@ -177,26 +348,147 @@ impl<'a> Parser<'a> {
                 self.ir.pc(address);
             }
             Keyword::Lab => {
-                let (label, span) = self.parse_identifier()?;
+                let (label, span) = match self.cursor.clone().advance_token() {
+                    token @ token_kind!(TokenKind::Add | TokenKind::Sub) => {
+                        self.cursor.advance_token();
+                        (Arc::from(&self.raw[token.span.as_range()]), token.span)
+                    }
+                    _ => self.parse_identifier()?,
+                };
                 self.ir.lab(label, span)?;
             }
             Keyword::Lih => {
-                // this code should work fine and its safe... but it don't...
-                // let conditional = self.parse_conditional()?;
-                // let address_tuple = self.parse_address_tuple()?;
-                // self.ir.lih(conditional, address_tuple);
-                let conditional: Conditional =
-                    unsafe { core::mem::transmute(self.parse_conditional()?) };
+                let conditional = self.parse_conditional()?;
                 let address_tuple = self.parse_address_tuple()?;
                 self.ir.lih(conditional, address_tuple);
             }
+            // Control Flow
+            Keyword::If => {
+                let conditional = self.parse_conditional()?;
+                let else_label = unique_label();
+                self.ir.lih(
+                    Conditional {
+                        kind: conditional.kind.negated(),
+                        ..conditional
+                    },
+                    label_address(else_label.clone()),
+                );
+                self.blocks.push(Block::If {
+                    else_label,
+                    end_label: None,
+                    span: keyword_span,
+                });
+            }
+            Keyword::Else => match self.blocks.last_mut() {
+                Some(Block::If {
+                    else_label,
+                    end_label: end_label @ None,
+                    ..
+                }) => {
+                    let label = unique_label();
+                    self.ir
+                        .pc(label_address(label.clone()))
+                        .lab(else_label.clone(), Span::new(0, 0))?;
+                    *end_label = Some(label);
+                }
+                _ => {
+                    return Err(Diagnostic {
+                        level: DiagLevel::Fatal,
+                        span: keyword_span,
+                        kind: DiagKind::UnexpectedElse,
+                    })
+                }
+            },
+            Keyword::While => {
+                let conditional = self.parse_conditional()?;
+                let (start_label, end_label) = (unique_label(), unique_label());
+                self.ir.lab(start_label.clone(), Span::new(0, 0))?.lih(
+                    Conditional {
+                        kind: conditional.kind.negated(),
+                        ..conditional
+                    },
+                    label_address(end_label.clone()),
+                );
+                self.blocks.push(Block::While {
+                    start_label,
+                    end_label,
+                    span: keyword_span,
+                });
+            }
+            Keyword::End => match self.blocks.pop() {
+                Some(Block::If {
+                    else_label,
+                    end_label,
+                    ..
+                }) => {
+                    self.ir
+                        .lab(end_label.unwrap_or(else_label), Span::new(0, 0))?;
+                }
+                Some(Block::While {
+                    start_label,
+                    end_label,
+                    ..
+                }) => {
+                    self.ir
+                        .pc(label_address(start_label))
+                        .lab(end_label, Span::new(0, 0))?;
+                }
+                None => {
+                    return Err(Diagnostic {
+                        level: DiagLevel::Fatal,
+                        span: keyword_span,
+                        kind: DiagKind::UnexpectedEnd,
+                    })
+                }
+            },
             // Miscellaneous
             Keyword::Nop => {
                 self.ir.nop();
             }
+            Keyword::Trp => {
+                self.ir.trp();
+            }
+            Keyword::Irt => {
+                self.ir.irt();
+            }
             Keyword::Hlt => {
                 self.ir.hlt();
             }
+            // Checks
+            Keyword::Assert => {
+                let conditional = self.parse_conditional()?;
+                self.checks.push(PendingCheck {
+                    span: Span::new(keyword_span.start, self.offset()),
+                    address: self.ir.next_address(),
+                    after: self.ir.last_instruction(),
+                    labels: self.ir.label_addresses().len(),
+                    left: PendingOperand::Either(conditional.left),
+                    kind: conditional.kind,
+                    right: PendingOperand::Either(conditional.right),
+                });
+            }
+            Keyword::Expect => {
+                let left = match self.cursor.clone().advance_token() {
+                    token_kind!(TokenKind::Register(_)) => {
+                        PendingOperand::Either(Either::Register(self.parse_register()?))
+                    }
+                    _ => PendingOperand::Memory(self.parse_memory()?),
+                };
+                match self.cursor.advance_token() {
+                    token_kind!(TokenKind::Eq) => (),
+                    unexpected => return Err(unexpected_token_error(unexpected, "Eq")),
+                }
+                let right = PendingOperand::Either(self.parse_either()?);
+                self.checks.push(PendingCheck {
+                    span: Span::new(keyword_span.start, self.offset()),
+                    address: self.ir.next_address(),
+                    after: self.ir.last_instruction(),
+                    labels: self.ir.label_addresses().len(),
+                    left,
+                    kind: ConditionalKind::Eq,
+                    right,
+                });
+            }
         };
 
         self.parse_end_of_line(true)?;
@@ -213,23 +505,79 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Parse an identifier referring to a label, qualifying it if it is local.
+    pub fn parse_reference(&mut self) -> Result<(Arc<str>, Span), Diagnostic> {
+        let (label, span) = self.parse_identifier()?;
+        Ok((self.ir.scoped(&label), span))
+    }
+
+    /// Parse a run of `+` or `-` referring to an anonymous label, e.g. `--` is the second previous.
+    fn parse_anonymous_reference(&mut self) -> Option<(Arc<str>, Span)> {
+        let mut clone = self.cursor.clone();
+        let first = clone.advance_token();
+        if !matches!(first.kind, TokenKind::Add | TokenKind::Sub) {
+            return None;
+        }
+
+        let mut span = first.span;
+        loop {
+            let mut lookahead = clone.clone();
+            match lookahead.advance_token() {
+                token if token.kind == first.kind && token.span.start == span.end => {
+                    span = span.merge(token.span);
+                    clone = lookahead;
+                }
+                // A sign followed by a number is a negative numeric.
+                token_kind!(TokenKind::Numeric { .. }) => return None,
+                _ => break,
+            }
+        }
+
+        self.cursor = clone;
+        let forward = first.kind == TokenKind::Add;
+        Some((self.ir.anonymous(forward, span.end - span.start), span))
+    }
+
     pub fn parse_address_tuple(&mut self) -> Result<AddressTuple, Diagnostic> {
+        if let Some((label, span)) = self.parse_anonymous_reference() {
+            return Ok(AddressTuple(
+                Either::Immediate(Immediate::LabelP0(label.clone(), u12::new(0), span)),
+                Either::Immediate(Immediate::LabelP1(label, u12::new(0), span)),
+            ));
+        }
+
         let mut clone = self.cursor.clone();
         if clone.advance_token().kind == TokenKind::Identifier
             && clone.advance_token().kind != TokenKind::Colon
         {
-            let token = self.cursor.advance_token();
-            let span = token.span;
-            let label: Arc<str> = Arc::from(&self.raw[span.as_range()]);
+            let (label, span) = self.parse_reference()?;
             Ok(AddressTuple(
-                Either::Immediate(Immediate::LabelP0(label.clone(), span)),
-                Either::Immediate(Immediate::LabelP1(label, span)),
+                Either::Immediate(Immediate::LabelP0(label.clone(), u12::new(0), span)),
+                Either::Immediate(Immediate::LabelP1(label, u12::new(0), span)),
             ))
         } else {
             Ok(AddressTuple(self.parse_either()?, self.parse_either()?))
         }
     }
 
+    /// Parse `MEM[address]`, the word an `EXPECT` reads.
+    fn parse_memory(&mut self) -> Result<AddressTuple, Diagnostic> {
+        match self.cursor.advance_token() {
+            token @ token_kind!(TokenKind::Identifier)
+                if &self.raw[token.span.as_range()] == "MEM" => {}
+            unexpected => return Err(unexpected_token_error(unexpected, "Register | MEM")),
+        }
+        match self.cursor.advance_token() {
+            token_kind!(TokenKind::OpenBracket) => (),
+            unexpected => return Err(unexpected_token_error(unexpected, "OpenBracket")),
+        }
+        let address = self.parse_address_tuple()?;
+        match self.cursor.advance_token() {
+            token_kind!(TokenKind::CloseBracket) => Ok(address),
+            unexpected => Err(unexpected_token_error(unexpected, "CloseBracket")),
+        }
+    }
+
     pub fn parse_either(&mut self) -> Result<Either, Diagnostic> {
         Ok(
             if let TokenKind::Register(_) = self.cursor.clone().advance_token().kind {
@@ -240,6 +588,17 @@ impl<'a> Parser<'a> {
         )
     }
 
+    pub fn parse_carry(&mut self) -> Result<Carry, Diagnostic> {
+        Ok(
+            if let TokenKind::Register(_) = self.cursor.clone().advance_token().kind {
+                Carry::Register(self.parse_register()?)
+            } else {
+                let (label, span) = self.parse_reference()?;
+                Carry::Label(label, span)
+            },
+        )
+    }
+
     pub fn parse_register(&mut self) -> Result<IrRegister, Diagnostic> {
         match self.cursor.advance_token() {
             token_kind!(TokenKind::Register(register)) => Ok(match register {
@@ -258,28 +617,28 @@ impl<'a> Parser<'a> {
         }
 
         let left = self.parse_either()?;
-        let condition = match self.cursor.advance_token() {
+        let (condition, operator_end) = match self.cursor.advance_token() {
             token_kind!(TokenKind::Eq) => match self.cursor.advance_token() {
-                token_kind!(TokenKind::Eq) => ConditionalKind::Eq,
+                token @ token_kind!(TokenKind::Eq) => (ConditionalKind::Eq, token.span.end),
                 unexpected => return Err(unexpected_token_error(unexpected, "Eq")),
             },
             token_kind!(TokenKind::Not) => match self.cursor.advance_token() {
-                token_kind!(TokenKind::Eq) => ConditionalKind::NotEq,
+                token @ token_kind!(TokenKind::Eq) => (ConditionalKind::NotEq, token.span.end),
                 unexpected => return Err(unexpected_token_error(unexpected, "Eq")),
             },
-            token_kind!(TokenKind::Greater) => match self.cursor.clone().advance_token() {
-                token_kind!(TokenKind::Eq) => {
+            token @ token_kind!(TokenKind::Greater) => match self.cursor.clone().advance_token() {
+                token @ token_kind!(TokenKind::Eq) => {
                     self.cursor.advance_token();
-                    ConditionalKind::GreaterEq
+                    (ConditionalKind::GreaterEq, token.span.end)
                 }
-                _ => ConditionalKind::Greater,
+                _ => (ConditionalKind::Greater, token.span.end),
             },
-            token_kind!(TokenKind::Less) => match self.cursor.clone().advance_token() {
-                token_kind!(TokenKind::Eq) => {
+            token @ token_kind!(TokenKind::Less) => match self.cursor.clone().advance_token() {
+                token @ token_kind!(TokenKind::Eq) => {
                     self.cursor.advance_token();
-                    ConditionalKind::LessEq
+                    (ConditionalKind::LessEq, token.span.end)
                 }
-                _ => ConditionalKind::Less,
+                _ => (ConditionalKind::Less, token.span.end),
             },
             unexpected => {
                 return Err(unexpected_token_error(
@@ -288,6 +647,17 @@ impl<'a> Parser<'a> {
                 ))
             }
         };
+        let condition = match self.parse_signed_suffix(operator_end) {
+            Some(suffix) => match condition {
+                ConditionalKind::Greater => ConditionalKind::SignedGreater,
+                ConditionalKind::GreaterEq => ConditionalKind::SignedGreaterEq,
+                ConditionalKind::Less => ConditionalKind::SignedLess,
+                ConditionalKind::LessEq => ConditionalKind::SignedLessEq,
+                // Equality doesn't depend on sign, so a suffix there is a mistake, not a no-op.
+                _ => return Err(unexpected_token_error(suffix, "Register | Immediate")),
+            },
+            None => condition,
+        };
         let right = self.parse_either()?;
 
         match self.cursor.advance_token() {
@@ -300,6 +670,21 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Consume an `S` directly after a comparison operator, e.g. `<S` or `>=S`.
+    fn parse_signed_suffix(&mut self, operator_end: usize) -> Option<Token> {
+        let mut clone = self.cursor.clone();
+        let token = clone.advance_token();
+        let is_suffix = token.kind == TokenKind::Identifier
+            && token.span.start == operator_end
+            && self.raw[token.span.as_range()].eq_ignore_ascii_case("S");
+
+        if is_suffix {
+            self.cursor = clone;
+            return Some(token);
+        }
+        None
+    }
+
     pub fn parse_immediate(&mut self) -> Result<Immediate, Diagnostic> {
         match self.cursor.advance_token() {
             token_kind!(TokenKind::OpenParen) => {
@@ -311,12 +696,13 @@ impl<'a> Parser<'a> {
                 }
             }
             token_kind!(TokenKind::Not) => Ok(Immediate::Not(Box::new(self.parse_immediate()?))),
+            sign @ token_kind!(TokenKind::Sub) => self.parse_negative_numeric(sign.span),
             token @ token_kind!(TokenKind::Identifier) => self.parse_label(token),
-            ref token @ token_kind!(TokenKind::Numeric { ref base, ref prefix_len }) => {
-                self.parse_numeric(token.span, base, prefix_len)
-            }
+            ref token @ token_kind!(TokenKind::Numeric { ref base, ref prefix_len }) => self
+                .parse_numeric(token.span, base, prefix_len)
+                .map(Immediate::Constant),
             ref token @ token_kind!(TokenKind::Character { ref terminated }) => {
-                self.parse_character(token.span, &terminated)
+                self.parse_character(token.span, terminated)
             }
             unexpected => Err(unexpected_token_error(
                 unexpected,
@@ -327,20 +713,21 @@ impl<'a> Parser<'a> {
 
     fn parse_block(&mut self, immediate: Immediate) -> Result<Immediate, Diagnostic> {
         let mut clone = self.cursor.clone();
-        let operator_builder = match clone.advance_token().kind {
-            TokenKind::And => |first, second| Immediate::And(first, second),
-            TokenKind::Or => |first, second| Immediate::Or(first, second),
-            TokenKind::Add => |first, second| Immediate::Add(first, second),
-            TokenKind::Sub => |first, second| Immediate::Sub(first, second),
-            TokenKind::Mul => |first, second| Immediate::Mul(first, second),
-            TokenKind::Div => |first, second| Immediate::Div(first, second),
+        let operator = clone.advance_token();
+        let operator_builder = match operator.kind {
+            TokenKind::And => |first, second, _| Immediate::And(first, second),
+            TokenKind::Or => |first, second, _| Immediate::Or(first, second),
+            TokenKind::Add => |first, second, _| Immediate::Add(first, second),
+            TokenKind::Sub => |first, second, _| Immediate::Sub(first, second),
+            TokenKind::Mul => |first, second, _| Immediate::Mul(first, second),
+            TokenKind::Div => Immediate::Div,
             TokenKind::Less if clone.advance_token().kind == TokenKind::Less => {
                 self.cursor.advance_token();
-                |first, second| Immediate::Rol(first, second)
+                |first, second, _| Immediate::Rol(first, second)
             }
             TokenKind::Greater if clone.advance_token().kind == TokenKind::Greater => {
                 self.cursor.advance_token();
-                |first, second| Immediate::Ror(first, second)
+                |first, second, _| Immediate::Ror(first, second)
             }
             _ => return Ok(immediate),
         };
@@ -349,29 +736,55 @@ impl<'a> Parser<'a> {
 
         let next = self.parse_immediate()?;
 
-        Ok(self.parse_block(operator_builder(Box::new(immediate), Box::new(next)))?)
+        self.parse_block(operator_builder(
+            Box::new(immediate),
+            Box::new(next),
+            operator.span,
+        ))
     }
 
-    fn parse_numeric(
-        &self,
-        span: Span,
-        base: &Base,
-        prefix_len: &usize,
-    ) -> Result<Immediate, Diagnostic> {
+    fn parse_numeric(&self, span: Span, base: &Base, prefix_len: &usize) -> Result<u6, Diagnostic> {
         let radix = match base {
             Base::Binary => 2,
             Base::Decimal => 10,
             Base::Hex => 16,
         };
 
-        let numeric = u6_from_str_radix(&self.raw[span.start + prefix_len..span.end], radix)
-            .map_err(|e| Diagnostic {
+        u6_from_str_radix(&self.raw[span.start + prefix_len..span.end], radix).map_err(|e| {
+            Diagnostic {
                 level: DiagLevel::Fatal,
                 span,
                 kind: DiagKind::ParseImmediate(e),
+            }
+        })
+    }
+
+    /// Parse the numeric after a minus sign as a two's complement value from -32 to 0.
+    fn parse_negative_numeric(&mut self, sign: Span) -> Result<Immediate, Diagnostic> {
+        let (span, base, prefix_len) = match self.cursor.advance_token() {
+            Token {
+                kind: TokenKind::Numeric { base, prefix_len },
+                span,
+            } => (span, base, prefix_len),
+            unexpected => return Err(unexpected_token_error(unexpected, "Numeric")),
+        };
+
+        let overflow = Diagnostic {
+            level: DiagLevel::Fatal,
+            span: sign.merge(span),
+            kind: DiagKind::ParseImmediate(IntErrorKind::NegOverflow),
+        };
+        let magnitude = self
+            .parse_numeric(span, &base, &prefix_len)
+            .map_err(|diagnostic| match diagnostic.kind {
+                DiagKind::ParseImmediate(IntErrorKind::PosOverflow) => overflow.clone(),
+                _ => diagnostic,
             })?;
+        if magnitude > u6::new(0b100000) {
+            return Err(overflow);
+        }
 
-        Ok(Immediate::Constant(numeric))
+        Ok(Immediate::Constant(u6::new(0).wrapping_sub(magnitude)))
     }
 
     fn parse_label(&mut self, first: Token) -> Result<Immediate, Diagnostic> {
@@ -380,14 +793,17 @@ impl<'a> Parser<'a> {
             unexpected => return Err(unexpected_token_error(unexpected, "Colon")),
         };
 
+        let label = self.ir.scoped(&self.raw[first.span.as_range()]);
         let num = self.cursor.advance_token();
         match &self.raw[num.span.as_range()] {
             "0" => Ok(Immediate::LabelP0(
-                Arc::from(&self.raw[first.span.as_range()]),
+                label,
+                u12::new(0),
                 first.span.merge(num.span),
             )),
             "1" => Ok(Immediate::LabelP1(
-                Arc::from(&self.raw[first.span.as_range()]),
+                label,
+                u12::new(0),
                 first.span.merge(num.span),
             )),
             _ => Err(unexpected_token_error(num, "Numeric(Decimal(`0` | `1`))")),
@@ -441,3 +857,108 @@ impl<'a> From<&'a str> for Parser<'a> {
         Self::new(value, u12::default())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_set(source: &str) -> Result<u6, Diagnostic> {
+        let result = Parser::from(source).parse();
+        if let Some(diagnostic) = result.diagnostics.into_iter().next() {
+            return Err(diagnostic);
+        }
+        match &result.ir[..] {
+            [Ir::Set(immediate)] => immediate.flatten(&result.symbol_table),
+            ir => panic!("expected a single SET found {ir:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_negative_numerics() {
+        assert_eq!(parse_set("SET -1"), Ok(u6::new(0b111111)));
+        assert_eq!(parse_set("SET -0"), Ok(u6::new(0)));
+        assert_eq!(parse_set("SET -32"), Ok(u6::new(0b100000)));
+        assert_eq!(parse_set("SET -0x5"), Ok(u6::new(59)));
+        assert_eq!(parse_set("SET (3 + -1)"), Ok(u6::new(2)));
+    }
+
+    #[test]
+    fn test_parse_negative_numeric_overflow() {
+        for source in ["SET -33", "SET -64", "SET -255"] {
+            assert_eq!(
+                parse_set(source).map_err(|diagnostic| diagnostic.kind),
+                Err(DiagKind::ParseImmediate(IntErrorKind::NegOverflow)),
+                "{source}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_division_by_zero() {
+        assert_eq!(parse_set("SET (7 / 2)"), Ok(u6::new(3)));
+        assert_eq!(
+            parse_set("SET (1 / 0)").map_err(|diagnostic| (diagnostic.kind, diagnostic.span)),
+            Err((DiagKind::DivisionByZero, Span::new(7, 8)))
+        );
+    }
+
+    #[test]
+    fn test_parse_signed_conditionals() {
+        let kind = |source: &str| Parser::from(source).parse_conditional().unwrap().kind;
+
+        assert_eq!(kind("[A <S B]"), ConditionalKind::SignedLess);
+        assert_eq!(kind("[A <=S B]"), ConditionalKind::SignedLessEq);
+        assert_eq!(kind("[A >S B]"), ConditionalKind::SignedGreater);
+        assert_eq!(kind("[A >=S B]"), ConditionalKind::SignedGreaterEq);
+        assert_eq!(kind("[A == S:0]"), ConditionalKind::Eq);
+        // Equality has no signed form.
+        assert!(Parser::from("[A ==S B]").parse_conditional().is_err());
+        assert!(Parser::from("[A !=S B]").parse_conditional().is_err());
+        assert_eq!(kind("[A < B]"), ConditionalKind::Less);
+        // The suffix has to be attached to the operator.
+        assert!(Parser::from("[A < S B]").parse_conditional().is_err());
+    }
+
+    #[test]
+    fn test_parse_checks() {
+        let result =
+            Parser::from("NOP\nASSERT [A >=S -1]\nEXPECT MEM[DATA] = 'X'\nHLT\nLAB DATA\nSET 0")
+                .parse();
+        assert!(result.diagnostics.is_empty());
+        // Checks compile to nothing and run before the next instruction.
+        assert_eq!(result.ir.len(), 3);
+
+        let checks = &result.debug_info.checks;
+        assert_eq!((checks[0].line, checks[0].address), (2, u12::new(1)));
+        assert_eq!(checks[0].kind, ConditionalKind::SignedGreaterEq);
+        assert_eq!(checks[0].right, Operand::Constant(u6::new(0b111111)));
+        assert_eq!(
+            (checks[0].after, checks[0].fall_through_only),
+            (Some(u12::new(0)), false)
+        );
+        assert_eq!(checks[1].span, Span::new(22, 44));
+        assert_eq!(
+            checks[1].left,
+            Operand::Memory(
+                Box::new(Operand::Constant(u6::new(0))),
+                Box::new(Operand::Constant(u6::new(2)))
+            )
+        );
+
+        // A label after a check at the same address is a way around it.
+        let result = Parser::from("EXPECT A = 0\nLAB AGAIN\nPC AGAIN").parse();
+        let check = &result.debug_info.checks[0];
+        assert_eq!((check.after, check.fall_through_only), (None, true));
+
+        let result = Parser::from("EXPECT MEM[MISSING] = 0").parse();
+        assert_eq!(result.diagnostics[0].kind, DiagKind::UndefinedLabel);
+        let result = Parser::from("EXPECT D = 0").parse();
+        assert!(matches!(
+            result.diagnostics[0].kind,
+            DiagKind::UnexpectedToken {
+                expected: "Register | MEM",
+                ..
+            }
+        ));
+    }
+}
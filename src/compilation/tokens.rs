@@ -1,4 +1,4 @@
-use strum::{Display as EnumDisplay, EnumString};
+use strum::{Display as EnumDisplay, EnumIter, EnumString, IntoStaticStr};
 
 use crate::compilation::span::Span;
 
@@ -71,7 +71,7 @@ pub enum TokenKind {
     Eof,
 }
 
-#[derive(Debug, PartialEq, EnumString, Clone)]
+#[derive(Debug, PartialEq, EnumString, EnumIter, IntoStaticStr, Clone)]
 #[strum(serialize_all = "UPPERCASE")]
 pub enum Keyword {
     // Logic
@@ -90,6 +90,13 @@ pub enum Keyword {
     // Arithmetic
     Add,
     Sub,
+    Mul,
+    Div,
+    Mod,
+    Adc,
+    Sbc,
+    Add12,
+    Inc12,
     // Memory
     Set,
     Mov,
@@ -99,9 +106,19 @@ pub enum Keyword {
     Pc,
     Lab,
     Lih,
+    // Control Flow
+    If,
+    Else,
+    While,
+    End,
     // Miscellaneous
     Nop,
+    Trp,
+    Irt,
     Hlt,
+    // Checks
+    Assert,
+    Expect,
 }
 
 #[derive(Debug, PartialEq, Clone)]
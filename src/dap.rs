@@ -0,0 +1,632 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeSet, VecDeque},
+    fs,
+    io::{self, stdin, stdout, Write},
+    path::PathBuf,
+    rc::Rc,
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+use arbitrary_int::{u12, u6};
+use serde_json::{json, Value};
+
+use crate::{
+    character_encoding::{decode_character, encode_character},
+    compilation::{compile_to_binary, CompileInfo, DiagLevel},
+    emulation::{ExecutionError, InteractiveState},
+    message::{read_message, write_message},
+    utils::{tuple_as_usize, usize_as_tuple},
+};
+
+/// How many instructions run between checks for a `pause` request.
+const PAUSE_POLL: usize = 1024;
+/// The machine has a single thread of execution and a single stack frame.
+const THREAD_ID: u64 = 1;
+const FRAME_ID: u64 = 1;
+const REGISTERS_REFERENCE: u64 = 1;
+
+/// How far a `continue` or step request runs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Run {
+    Continue,
+    Statement,
+    Instruction,
+}
+
+/// Why execution stopped.
+#[derive(Debug, PartialEq)]
+enum Stop {
+    Step,
+    Breakpoint,
+    Pause,
+    Halt,
+    Fault(ExecutionError),
+}
+
+/// What to do once a request has been answered.
+enum After {
+    Nothing,
+    Initialized,
+    Start,
+    Resume(Run),
+    Disconnect,
+}
+
+struct Program {
+    path: PathBuf,
+    compile_info: CompileInfo,
+}
+
+struct Session<W: Write> {
+    writer: W,
+    incoming: Receiver<Value>,
+    /// Requests that arrived while the program was running.
+    pending: VecDeque<Value>,
+    seq: u64,
+    state: InteractiveState,
+    program: Option<Program>,
+    breakpoints: BTreeSet<usize>,
+    stop_on_entry: bool,
+    /// Text written with `TRP`, sent to the editor as `output` events.
+    output: Rc<RefCell<String>>,
+}
+
+/// Speak the Debug Adapter Protocol over stdin and stdout until the editor disconnects.
+pub fn serve() -> io::Result<()> {
+    let (sender, incoming) = mpsc::channel();
+    // Reading on a separate thread lets a running program notice a `pause`.
+    thread::spawn(move || {
+        let mut stdin = stdin().lock();
+        while let Ok(Some(message)) = read_message(&mut stdin) {
+            if sender.send(message).is_err() {
+                break;
+            }
+        }
+    });
+    Session::new(stdout().lock(), incoming).run()
+}
+
+impl<W: Write> Session<W> {
+    fn new(writer: W, incoming: Receiver<Value>) -> Self {
+        let output = Rc::new(RefCell::new(String::new()));
+        let mut state = InteractiveState::new();
+        state.trap_handler = Some(trap_handler(output.clone()));
+
+        Self {
+            writer,
+            incoming,
+            pending: VecDeque::new(),
+            seq: 0,
+            state,
+            program: None,
+            breakpoints: BTreeSet::new(),
+            stop_on_entry: false,
+            output,
+        }
+    }
+
+    fn run(mut self) -> io::Result<()> {
+        loop {
+            let request = match self.pending.pop_front() {
+                Some(request) => request,
+                None => match self.incoming.recv() {
+                    Ok(request) => request,
+                    Err(_) => return Ok(()),
+                },
+            };
+            if !self.handle(&request)? {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Answer one request, returning false once the session is over.
+    fn handle(&mut self, request: &Value) -> io::Result<bool> {
+        let command = request["command"].as_str().unwrap_or_default();
+        let arguments = &request["arguments"];
+
+        let (result, after) = match command {
+            "initialize" => (
+                Ok(json!({
+                    "supportsConfigurationDoneRequest": true,
+                    "supportsReadMemoryRequest": true,
+                    "supportsSteppingGranularity": true,
+                })),
+                After::Nothing,
+            ),
+            "launch" => match self.launch(arguments) {
+                Ok(()) => (Ok(json!({})), After::Initialized),
+                Err(message) => (Err(message), After::Nothing),
+            },
+            "setBreakpoints" => (Ok(self.set_breakpoints(arguments)), After::Nothing),
+            "setExceptionBreakpoints" | "pause" => (Ok(json!({})), After::Nothing),
+            "configurationDone" => (Ok(json!({})), After::Start),
+            "threads" => (
+                Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] })),
+                After::Nothing,
+            ),
+            "stackTrace" => (Ok(self.stack_trace()), After::Nothing),
+            "scopes" => (
+                Ok(json!({ "scopes": [{
+                    "name": "Registers",
+                    "presentationHint": "registers",
+                    "variablesReference": REGISTERS_REFERENCE,
+                    "expensive": false,
+                }] })),
+                After::Nothing,
+            ),
+            "variables" => (Ok(self.variables(arguments)), After::Nothing),
+            "readMemory" => (self.read_memory(arguments), After::Nothing),
+            "continue" => (
+                Ok(json!({ "allThreadsContinued": true })),
+                After::Resume(Run::Continue),
+            ),
+            // There are no calls to step into or out of, so every step moves one statement.
+            "next" | "stepIn" | "stepOut" => {
+                let run = match arguments["granularity"].as_str() {
+                    Some("instruction") => Run::Instruction,
+                    _ => Run::Statement,
+                };
+                (Ok(json!({})), After::Resume(run))
+            }
+            "disconnect" | "terminate" => (Ok(json!({})), After::Disconnect),
+            _ => (
+                Err(format!("Unsupported request `{command}`")),
+                After::Nothing,
+            ),
+        };
+
+        self.respond(request, result)?;
+        match after {
+            After::Nothing => (),
+            After::Initialized => self.event("initialized", json!({}))?,
+            After::Start if self.stop_on_entry => self.stopped("entry", None)?,
+            After::Start => self.resume(Run::Continue)?,
+            After::Resume(run) => self.resume(run)?,
+            After::Disconnect => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn launch(&mut self, arguments: &Value) -> Result<(), String> {
+        let path = PathBuf::from(arguments["program"].as_str().ok_or("Missing `program`")?);
+        let code = fs::read_to_string(&path)
+            .map_err(|error| format!("Could not read `{}`: {error}", path.display()))?;
+        let compile_info = compile_to_binary(&code.to_uppercase(), u12::new(0));
+
+        let errors: Vec<_> = compile_info
+            .diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.level == DiagLevel::Fatal)
+            .map(|diagnostic| {
+                format!(
+                    "line {}: {}",
+                    diagnostic.line(&code),
+                    diagnostic.kind.help()
+                )
+            })
+            .collect();
+        if !errors.is_empty() {
+            return Err(format!(
+                "Could not compile `{}`\n{}",
+                path.display(),
+                errors.join("\n")
+            ));
+        }
+
+        self.state
+            .memory
+            .store_array(0, &compile_info.binary)
+            .map_err(|error| error.to_string())?;
+        self.state.program_counter.set(usize_as_tuple(0));
+        self.state.checks = compile_info.debug_info.checks.clone();
+        self.stop_on_entry = arguments["stopOnEntry"].as_bool().unwrap_or(false);
+        // Editors match frames to open files by their absolute path.
+        let path = fs::canonicalize(&path).unwrap_or(path);
+        self.program = Some(Program { path, compile_info });
+        Ok(())
+    }
+
+    /// Breakpoints move to the next line with code, like most debuggers do.
+    fn set_breakpoints(&mut self, arguments: &Value) -> Value {
+        let same_source = |program: &&Program| {
+            let path = PathBuf::from(arguments["source"]["path"].as_str().unwrap_or_default());
+            fs::canonicalize(path).ok() == fs::canonicalize(&program.path).ok()
+        };
+        let statements = self
+            .program
+            .as_ref()
+            .filter(same_source)
+            .map(|program| &program.compile_info.debug_info.statements[..])
+            .unwrap_or_default();
+
+        self.breakpoints.clear();
+        let breakpoints: Vec<_> = arguments["breakpoints"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|breakpoint| {
+                let line = breakpoint["line"].as_u64().unwrap_or_default() as usize;
+                match statements
+                    .iter()
+                    .find(|statement| statement.line >= line && !statement.data)
+                {
+                    Some(statement) => {
+                        self.breakpoints.insert(statement.address.value() as usize);
+                        json!({ "verified": true, "line": statement.line })
+                    }
+                    None => json!({ "verified": false, "line": line }),
+                }
+            })
+            .collect();
+        json!({ "breakpoints": breakpoints })
+    }
+
+    fn stack_trace(&self) -> Value {
+        let program_counter = tuple_as_usize(self.state.program_counter.as_tuple());
+        let mut frame = json!({
+            "id": FRAME_ID,
+            "name": format!("0x{program_counter:03X}"),
+            "line": 0,
+            "column": 1,
+            "instructionPointerReference": format!("0x{program_counter:03X}"),
+        });
+        if let Some(program) = &self.program {
+            frame["source"] = json!({ "path": program.path });
+            if let Some(statement) = program
+                .compile_info
+                .debug_info
+                .statement_at(u12::new(program_counter as u16))
+            {
+                frame["line"] = json!(statement.line);
+            }
+        }
+        json!({ "stackFrames": [frame], "totalFrames": 1 })
+    }
+
+    fn variables(&self, arguments: &Value) -> Value {
+        if arguments["variablesReference"].as_u64() != Some(REGISTERS_REFERENCE) {
+            return json!({ "variables": [] });
+        }
+
+        let register = |name: &str, value: u6| {
+            json!({
+                "name": name,
+                "value": format!("{value:0>6b} ({value})"),
+                "variablesReference": 0,
+            })
+        };
+        let program_counter = tuple_as_usize(self.state.program_counter.as_tuple());
+        json!({ "variables": [
+            register("A", self.state.a),
+            register("B", self.state.b),
+            register("C", self.state.c),
+            {
+                "name": "PC",
+                "value": format!("0x{program_counter:03X}"),
+                "variablesReference": 0,
+                "memoryReference": format!("0x{program_counter:03X}"),
+            },
+        ] })
+    }
+
+    /// One byte per word; the read stops at the end of memory or the unmapped range.
+    fn read_memory(&self, arguments: &Value) -> Result<Value, String> {
+        let reference = arguments["memoryReference"].as_str().unwrap_or_default();
+        let address = usize::from_str_radix(reference.trim_start_matches("0x"), 16)
+            .map_err(|_| format!("Invalid memory reference `{reference}`"))?;
+        let start =
+            address.saturating_add_signed(arguments["offset"].as_i64().unwrap_or(0) as isize);
+        let count = arguments["count"].as_u64().unwrap_or(0) as usize;
+
+        let words: Vec<u8> = (start..start.saturating_add(count))
+            .map_while(|address| {
+                (address < 4096)
+                    .then(|| self.state.memory.read(usize_as_tuple(address)).ok())
+                    .flatten()
+            })
+            .map(|word| word.value())
+            .collect();
+        Ok(json!({
+            "address": format!("0x{start:03X}"),
+            "data": base64(&words),
+            "unreadableBytes": count - words.len(),
+        }))
+    }
+
+    fn resume(&mut self, run: Run) -> io::Result<()> {
+        // `HLT` waits for an interrupt the editor can't raise, so the program is over.
+        if self.state.is_halt() == Ok(true) {
+            return self.event("terminated", json!({}));
+        }
+
+        let stop = self.execute(run);
+        let output = self.output.take();
+        if !output.is_empty() {
+            self.event("output", json!({ "category": "stdout", "output": output }))?;
+        }
+
+        match stop {
+            Stop::Step => self.stopped("step", None),
+            Stop::Breakpoint => self.stopped("breakpoint", None),
+            Stop::Pause => self.stopped("pause", None),
+            Stop::Halt => self.stopped("halt", Some("Reached HLT".to_string())),
+            Stop::Fault(error) => self.stopped("exception", Some(format!("fault: {error}"))),
+        }
+    }
+
+    fn execute(&mut self, run: Run) -> Stop {
+        let mut count = 0;
+        loop {
+            if let Err(error) = self.state.consume_instruction() {
+                return Stop::Fault(error);
+            }
+            let program_counter = tuple_as_usize(self.state.program_counter.as_tuple());
+            if self.state.is_halt() == Ok(true) {
+                return match self.state.check() {
+                    Ok(()) => Stop::Halt,
+                    Err(error) => Stop::Fault(error),
+                };
+            }
+            if self.breakpoints.contains(&program_counter) {
+                return Stop::Breakpoint;
+            }
+            match run {
+                Run::Instruction => return Stop::Step,
+                Run::Statement if self.is_statement_start(program_counter) => return Stop::Step,
+                _ => (),
+            }
+
+            count += 1;
+            if count % PAUSE_POLL == 0 && self.pause_requested() {
+                return Stop::Pause;
+            }
+        }
+    }
+
+    fn is_statement_start(&self, address: usize) -> bool {
+        let Some(program) = &self.program else {
+            return true;
+        };
+        program
+            .compile_info
+            .debug_info
+            .statement_at(u12::new(address as u16))
+            .is_some_and(|statement| statement.address.value() as usize == address)
+    }
+
+    /// Queue requests that arrived while running; a `pause` is answered once execution stops.
+    fn pause_requested(&mut self) -> bool {
+        let mut paused = false;
+        while let Ok(request) = self.incoming.try_recv() {
+            paused |= request["command"] == "pause";
+            self.pending.push_back(request);
+        }
+        paused
+    }
+
+    fn stopped(&mut self, reason: &str, text: Option<String>) -> io::Result<()> {
+        let mut body = json!({
+            "reason": reason,
+            "threadId": THREAD_ID,
+            "allThreadsStopped": true,
+        });
+        if let Some(text) = text {
+            body["description"] = json!(text);
+            body["text"] = json!(text);
+        }
+        self.event("stopped", body)
+    }
+
+    fn respond(&mut self, request: &Value, result: Result<Value, String>) -> io::Result<()> {
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": result.is_ok(),
+        });
+        match result {
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = json!(message),
+        }
+        self.send(response)
+    }
+
+    fn event(&mut self, event: &str, body: Value) -> io::Result<()> {
+        self.send(json!({ "type": "event", "event": event, "body": body }))
+    }
+
+    fn send(&mut self, mut message: Value) -> io::Result<()> {
+        self.seq += 1;
+        message["seq"] = json!(self.seq);
+        write_message(&mut self.writer, &message)
+    }
+}
+
+/// The services of the command line runner, except that output goes to the editor and there is no input.
+fn trap_handler(output: Rc<RefCell<String>>) -> Box<dyn FnMut(&mut InteractiveState)> {
+    Box::new(move |state| match state.a.value() {
+        0 => output.borrow_mut().push(*decode_character(&state.c)),
+        1 => output.borrow_mut().push_str(&format!("{}\n", state.c)),
+        2 => state.c = *encode_character(&' ').unwrap(),
+        _ => (),
+    })
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut text = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (index, byte)| {
+            group | u32::from(*byte) << (16 - index * 8)
+        });
+        for index in 0..4 {
+            if index <= chunk.len() {
+                text.push(ALPHABET[(group >> (18 - index * 6)) as usize & 0b111111] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        io::Cursor,
+        sync::mpsc::{self, Sender},
+    };
+
+    use serde_json::{json, Value};
+
+    use super::{base64, Session};
+    use crate::message::read_message;
+
+    fn new_session(source: &str, name: &str) -> (Session<Vec<u8>>, Sender<Value>, String) {
+        let path = std::env::temp_dir().join(format!("dianac-dap-{name}.dcl"));
+        fs::write(&path, source).unwrap();
+        let (sender, incoming) = mpsc::channel();
+        (
+            Session::new(Vec::new(), incoming),
+            sender,
+            path.to_string_lossy().into_owned(),
+        )
+    }
+
+    fn request(session: &mut Session<Vec<u8>>, command: &str, arguments: Value) -> Vec<Value> {
+        session.writer.clear();
+        session
+            .handle(
+                &json!({ "seq": 1, "type": "request", "command": command, "arguments": arguments }),
+            )
+            .unwrap();
+
+        let mut reader = Cursor::new(&session.writer);
+        std::iter::from_fn(|| read_message(&mut reader).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_launch_and_breakpoints() {
+        let (mut session, _sender, path) =
+            new_session("NOR A 1\n\nMOV B 2\n# COMMENT\nNOR C 3\nHLT", "breakpoints");
+
+        let messages = request(&mut session, "launch", json!({ "program": path }));
+        assert_eq!(messages[0]["success"], true);
+        assert_eq!(messages[1]["event"], "initialized");
+
+        let messages = request(
+            &mut session,
+            "setBreakpoints",
+            json!({ "source": { "path": path }, "breakpoints": [{ "line": 4 }, { "line": 9 }] }),
+        );
+        assert_eq!(
+            messages[0]["body"]["breakpoints"],
+            json!([{ "verified": true, "line": 5 }, { "verified": false, "line": 9 }])
+        );
+
+        let messages = request(&mut session, "configurationDone", json!({}));
+        assert_eq!(messages[1]["event"], "stopped");
+        assert_eq!(messages[1]["body"]["reason"], "breakpoint");
+
+        let messages = request(&mut session, "stackTrace", json!({ "threadId": 1 }));
+        assert_eq!(messages[0]["body"]["stackFrames"][0]["line"], 5);
+
+        let messages = request(
+            &mut session,
+            "variables",
+            json!({ "variablesReference": 1 }),
+        );
+        let variables = &messages[0]["body"]["variables"];
+        assert_eq!(variables[0]["value"], "111110 (62)");
+        assert_eq!(variables[1]["name"], "B");
+        assert_eq!(variables[3]["value"], variables[3]["memoryReference"]);
+
+        let messages = request(&mut session, "continue", json!({ "threadId": 1 }));
+        assert_eq!(messages[1]["body"]["reason"], "halt");
+        let messages = request(&mut session, "continue", json!({ "threadId": 1 }));
+        assert_eq!(messages[1]["event"], "terminated");
+    }
+
+    #[test]
+    fn test_stepping() {
+        let (mut session, _sender, path) =
+            new_session("NOR A 1\nIF [A == 1]\nNOP\nEND\nHLT", "stepping");
+        request(
+            &mut session,
+            "launch",
+            json!({ "program": path, "stopOnEntry": true }),
+        );
+        let messages = request(&mut session, "configurationDone", json!({}));
+        assert_eq!(messages[1]["body"]["reason"], "entry");
+
+        let line = |session: &mut Session<Vec<u8>>| {
+            request(session, "stackTrace", json!({ "threadId": 1 }))[0]["body"]["stackFrames"][0]
+                ["line"]
+                .clone()
+        };
+
+        // A whole `IF` condition is one step
+        request(&mut session, "next", json!({ "threadId": 1 }));
+        assert_eq!(line(&mut session), 2);
+        request(&mut session, "next", json!({ "threadId": 1 }));
+        assert_eq!(line(&mut session), 5);
+
+        let (mut session, _sender, path) = new_session("NOR A 1\nHLT", "instruction");
+        request(&mut session, "launch", json!({ "program": path }));
+        let messages = request(
+            &mut session,
+            "stepIn",
+            json!({ "threadId": 1, "granularity": "instruction" }),
+        );
+        assert_eq!(messages[1]["body"]["reason"], "halt");
+    }
+
+    #[test]
+    fn test_memory_and_output() {
+        let (mut session, _sender, path) =
+            new_session("NOR A 0b111110\nNOR C 0b111010\nTRP\nHLT\nSET 5", "memory");
+        request(&mut session, "launch", json!({ "program": path }));
+
+        let messages = request(&mut session, "configurationDone", json!({}));
+        assert_eq!(messages[1]["event"], "output");
+        assert_eq!(messages[1]["body"]["output"], "5\n");
+
+        let messages = request(
+            &mut session,
+            "readMemory",
+            json!({ "memoryReference": "0x005", "offset": 1, "count": 2 }),
+        );
+        assert_eq!(messages[0]["body"]["address"], "0x006");
+        assert_eq!(messages[0]["body"]["data"], base64(&[5, 0]));
+
+        let messages = request(
+            &mut session,
+            "readMemory",
+            json!({ "memoryReference": "0xF3F", "count": 3 }),
+        );
+        assert_eq!(messages[0]["body"]["unreadableBytes"], 2);
+    }
+
+    #[test]
+    fn test_compile_error() {
+        let (mut session, _sender, path) = new_session("NOR A 1\nMOV Q 1", "error");
+        let messages = request(&mut session, "launch", json!({ "program": path }));
+        assert_eq!(messages[0]["success"], false);
+        assert!(messages[0]["message"].as_str().unwrap().contains("line 2"));
+        assert_eq!(messages.len(), 1);
+    }
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foob"), "Zm9vYg==");
+    }
+}
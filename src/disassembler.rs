@@ -0,0 +1,95 @@
+use arbitrary_int::u6;
+
+use crate::instruction::{Instruction, Operation, Register};
+
+/// One decoded statement and the words it was read from.
+#[derive(Debug, PartialEq)]
+pub struct Line {
+    pub address: usize,
+    pub words: Vec<u6>,
+    pub text: String,
+}
+
+fn register_name(register: Register) -> &'static str {
+    match register {
+        Register::A => "A",
+        Register::B => "B",
+        Register::C => "C",
+        Register::Immediate => unreachable!(),
+    }
+}
+
+/// Decode `binary` loaded at `offset` into DCL statements, reading immediates inline.
+pub fn disassemble(binary: &[u6], offset: usize) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut index = 0;
+
+    while index < binary.len() {
+        let start = index;
+        let raw_value = binary[index];
+        index += 1;
+
+        let text = match raw_value.value() {
+            0b001100 => "NOP".to_string(),
+            0b001101 => "TRP".to_string(),
+            0b001110 => "IRT".to_string(),
+            0b001111 => "HLT".to_string(),
+            _ => {
+                let instruction = Instruction::new_with_raw_value(raw_value);
+                let mut operand = |register: Register| match register {
+                    Register::Immediate => {
+                        let value = binary.get(index).map(|value| value.to_string());
+                        index += 1;
+                        value.unwrap_or_else(|| "?".to_string())
+                    }
+                    register => register_name(register).to_string(),
+                };
+                let (one, two) = (operand(instruction.one()), operand(instruction.two()));
+
+                let keyword = match instruction.operation() {
+                    Operation::Nor => "NOR",
+                    Operation::Pc => "PC",
+                    Operation::Load => "LOD",
+                    Operation::Store => "STO",
+                };
+                format!("{keyword} {one} {two}")
+            }
+        };
+
+        lines.push(Line {
+            address: offset + start,
+            words: binary[start..index.min(binary.len())].to_vec(),
+            text,
+        });
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use arbitrary_int::u12;
+
+    use super::disassemble;
+    use crate::compilation::compile_to_binary;
+
+    #[test]
+    fn test_disassemble_round_trip() {
+        let source = "NOR A B\nPC 1 2\nLOD C 3\nSTO 4 A\nNOP\nHLT";
+        let binary = compile_to_binary(source, u12::new(0)).binary;
+        let lines = disassemble(&binary, 10);
+
+        let text: Vec<_> = lines.iter().map(|line| line.text.as_str()).collect();
+        assert_eq!(text, source.lines().collect::<Vec<_>>());
+        let addresses: Vec<_> = lines.iter().map(|line| line.address).collect();
+        assert_eq!(addresses, vec![10, 11, 14, 16, 18, 19]);
+    }
+
+    #[test]
+    fn test_disassemble_truncated_immediate() {
+        let binary = compile_to_binary("PC 1 2", u12::new(0)).binary;
+        let lines = disassemble(&binary[..2], 0);
+        assert_eq!(lines[0].text, "PC 1 ?");
+        assert_eq!(lines[0].words.len(), 2);
+    }
+}
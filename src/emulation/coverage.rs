@@ -0,0 +1,56 @@
+use std::{fmt::Write, path::Path};
+
+use crate::compilation::debug_info::DebugInfo;
+
+use super::Profile;
+
+/// An lcov tracefile for `source`, built from the executions in `profile`.
+///
+/// A line counts as hit as often as its most executed word; `SET` data is left out. The `HLT` at
+/// `halted_at` is reached but never executed, so it counts once more. Each `LIH` is a branch block:
+/// branch 0 jumps to the target, branch 1 falls through.
+pub fn lcov(
+    profile: &Profile,
+    halted_at: Option<usize>,
+    source: &Path,
+    debug_info: &DebugInfo,
+) -> String {
+    let mut text = String::new();
+    writeln!(text, "TN:\nSF:{}", source.display()).unwrap();
+
+    let (mut branches_found, mut branches_hit) = (0, 0);
+    for (block, branch) in debug_info.branches.iter().enumerate() {
+        let dispatched = profile.executions(branch.dispatch.value() as usize);
+        let taken = profile.executions(branch.taken.value() as usize);
+        // Only the dispatch leads to `taken`, unless profiling started after the dispatch ran.
+        let fell_through = dispatched.saturating_sub(taken);
+        for (index, count) in [taken, fell_through].into_iter().enumerate() {
+            branches_found += 1;
+            if dispatched == 0 {
+                writeln!(text, "BRDA:{},{block},{index},-", branch.line).unwrap();
+            } else {
+                branches_hit += (count > 0) as usize;
+                writeln!(text, "BRDA:{},{block},{index},{count}", branch.line).unwrap();
+            }
+        }
+    }
+    writeln!(text, "BRF:{branches_found}\nBRH:{branches_hit}").unwrap();
+
+    let (mut lines_found, mut lines_hit) = (0, 0);
+    for statement in debug_info
+        .statements
+        .iter()
+        .filter(|statement| !statement.data)
+    {
+        let start = statement.address.value() as usize;
+        let hits = (start..start + statement.length.value() as usize)
+            .map(|address| profile.executions(address) + (Some(address) == halted_at) as u64)
+            .max()
+            .unwrap_or_default();
+        lines_found += 1;
+        lines_hit += (hits > 0) as usize;
+        writeln!(text, "DA:{},{hits}", statement.line).unwrap();
+    }
+    writeln!(text, "LF:{lines_found}\nLH:{lines_hit}\nend_of_record").unwrap();
+    text
+}
@@ -0,0 +1,45 @@
+use std::fmt;
+
+use arbitrary_int::u6;
+
+use crate::utils::tuple_as_usize;
+
+/// A fault raised by the emulated machine instead of panicking the host.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ExecutionError {
+    /// `TRP` or `IRT` while the machine is in strict mode.
+    IllegalOpcode(u6),
+    /// `IRT` without a pending interrupt to return from.
+    ReturnWithoutInterrupt,
+    /// A `STORE` into the read-only range `0xF3E..=0xFFF`.
+    WriteToRom((u6, u6)),
+    /// A fetch or `LOAD` from the unmapped range `0xF40..=0xF7F`.
+    UnmappedRead((u6, u6)),
+    /// A program that does not fit in RAM at the requested offset.
+    OutOfRangeLoad { offset: usize, length: usize },
+    /// An `ASSERT` or `EXPECT` that did not hold when execution reached it.
+    FailedCheck { line: usize },
+}
+
+impl fmt::Display for ExecutionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IllegalOpcode(opcode) => write!(f, "illegal opcode {opcode:0>6b}"),
+            Self::ReturnWithoutInterrupt => write!(f, "interrupt return without an interrupt"),
+            Self::WriteToRom(address) => {
+                write!(f, "write to ROM address {:#05X}", tuple_as_usize(*address))
+            }
+            Self::UnmappedRead(address) => {
+                write!(
+                    f,
+                    "read from unmapped address {:#05X}",
+                    tuple_as_usize(*address)
+                )
+            }
+            Self::OutOfRangeLoad { offset, length } => {
+                write!(f, "{length} words at {offset} do not fit in RAM")
+            }
+            Self::FailedCheck { line } => write!(f, "check on line {line} failed"),
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use std::collections::VecDeque;
+
+use arbitrary_int::u6;
+
+/// Everything needed to undo one step or interrupt.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Delta {
+    pub program_counter: (u6, u6),
+    pub registers: (u6, u6, u6),
+    pub cycles: u64,
+    pub interrupt_return: Option<(u6, u6)>,
+    pub last_instruction: Option<(u6, u6)>,
+    /// The cells the step overwrote and their old contents, see `Memory::cell`.
+    pub replaced: Vec<((u6, u6), Option<u6>)>,
+}
+
+/// The most recent deltas, oldest are dropped once `capacity` is reached.
+pub struct History {
+    deltas: VecDeque<Delta>,
+    capacity: usize,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            deltas: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, delta: Delta) {
+        if self.deltas.len() == self.capacity {
+            self.deltas.pop_front();
+        }
+        if self.capacity > 0 {
+            self.deltas.push_back(delta);
+        }
+    }
+
+    pub fn pop(&mut self) -> Option<Delta> {
+        self.deltas.pop_back()
+    }
+
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
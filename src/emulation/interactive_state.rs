@@ -1,8 +1,25 @@
 use arbitrary_int::u6;
 
-use crate::instruction::{Instruction, Operation, Register};
+use crate::{
+    compilation::{
+        debug_info::{Check, Operand},
+        ir::IrRegister,
+    },
+    instruction::{Instruction, Operation, Register},
+    utils::tuple_as_usize,
+};
 
-use super::{memory::Memory, program_counter::ProgramCounter};
+use super::{
+    history::{Delta, History},
+    memory::Memory,
+    profile::Profile,
+    program_counter::ProgramCounter,
+    trace::{MemoryWrite, Trace, TraceEntry},
+    ExecutionError,
+};
+
+/// Called by `TRP` so the host can service a request using the machine's registers and memory.
+pub type TrapHandler = Box<dyn FnMut(&mut InteractiveState)>;
 
 pub struct InteractiveState {
     pub a: u6,
@@ -10,6 +27,41 @@ pub struct InteractiveState {
     pub c: u6,
     pub memory: Memory,
     pub program_counter: ProgramCounter,
+    /// Words fetched so far, one per opcode plus one per immediate operand.
+    pub cycles: u64,
+    /// The address `IRT` returns to, saved when an interrupt is raised.
+    pub interrupt_return: Option<(u6, u6)>,
+    pub trap_handler: Option<TrapHandler>,
+    /// Treat `TRP` and `IRT` as illegal opcodes, like the original hardware.
+    pub strict: bool,
+    pub trace: Option<Trace>,
+    pub profile: Option<Profile>,
+    /// Undo information for `step_back`, recorded while this is set.
+    pub history: Option<History>,
+    /// `ASSERT` and `EXPECT` checks of the loaded program, in address order.
+    pub checks: Vec<Check>,
+    /// Where the last executed instruction started, to tell falling into a check from jumping to it.
+    pub last_instruction: Option<(u6, u6)>,
+}
+
+/// What a single instruction read and wrote, used to build trace entries.
+pub struct Step {
+    pub raw_value: u6,
+    pub operands: Option<(u6, u6)>,
+    pub write: Option<MemoryWrite>,
+    /// Memory cells and what they held before, for undoing the step.
+    pub replaced: Vec<((u6, u6), Option<u6>)>,
+}
+
+impl Step {
+    fn special(raw_value: u6) -> Self {
+        Self {
+            raw_value,
+            operands: None,
+            write: None,
+            replaced: Vec::new(),
+        }
+    }
 }
 
 impl InteractiveState {
@@ -23,55 +75,207 @@ impl InteractiveState {
             c: u6::default(),
             memory: Memory::new(program_counter.clone()),
             program_counter,
+            cycles: 0,
+            interrupt_return: None,
+            trap_handler: None,
+            strict: false,
+            trace: None,
+            profile: None,
+            history: None,
+            checks: Vec::new(),
+            last_instruction: None,
+        }
+    }
+
+    /// Save the program counter for `IRT` and jump to `vector`; a halted machine resumes after its `HLT`.
+    pub fn interrupt(&mut self, vector: (u6, u6)) {
+        if let Some(history) = &mut self.history {
+            history.push(Delta {
+                program_counter: self.program_counter.as_tuple(),
+                registers: (self.a, self.b, self.c),
+                cycles: self.cycles,
+                interrupt_return: self.interrupt_return,
+                last_instruction: self.last_instruction,
+                replaced: Vec::new(),
+            });
+        }
+
+        if self.is_halt() == Ok(true) {
+            self.program_counter.increment();
+        }
+        self.interrupt_return = Some(self.program_counter.as_tuple());
+        self.program_counter.set(vector);
+    }
+
+    pub fn consume_until_halt(&mut self) -> Result<(), ExecutionError> {
+        while !self.is_halt()? {
+            self.consume_instruction()?;
+        }
+        self.check()
+    }
+
+    /// The first check at the program counter that doesn't hold, with its left and right values.
+    pub fn failed_check(&self) -> Option<(&Check, u6, u6)> {
+        let address = tuple_as_usize(self.program_counter.as_tuple());
+        let start = self
+            .checks
+            .partition_point(|check| (check.address.value() as usize) < address);
+        self.checks[start..]
+            .iter()
+            .take_while(|check| check.address.value() as usize == address)
+            .filter(|check| {
+                !check.fall_through_only
+                    || check.after.map(|after| after.value() as usize)
+                        == self.last_instruction.map(tuple_as_usize)
+            })
+            .map(|check| (check, self.operand(&check.left), self.operand(&check.right)))
+            .find(|(check, left, right)| !check.kind.holds(*left, *right))
+    }
+
+    /// Fail if a check at the program counter doesn't hold.
+    pub fn check(&self) -> Result<(), ExecutionError> {
+        match self.failed_check() {
+            Some((check, ..)) => Err(ExecutionError::FailedCheck { line: check.line }),
+            None => Ok(()),
         }
     }
 
-    pub fn consume_until_halt(&mut self) {
-        while !self.is_halt() {
-            self.consume_instruction();
+    fn operand(&self, operand: &Operand) -> u6 {
+        match operand {
+            Operand::Register(IrRegister::A) => self.a,
+            Operand::Register(IrRegister::B) => self.b,
+            Operand::Register(IrRegister::C) => self.c,
+            Operand::Constant(value) => *value,
+            Operand::Memory(high, low) => self
+                .memory
+                .read((self.operand(high), self.operand(low)))
+                .unwrap_or_default(),
         }
     }
 
-    pub fn is_halt(&self) -> bool {
-        self.memory.read(self.program_counter.as_tuple()) == u6::new(0b001111)
+    /// Whether the program counter is on a `HLT`; fails if it points at unmapped memory.
+    pub fn is_halt(&self) -> Result<bool, ExecutionError> {
+        Ok(self.memory.read(self.program_counter.as_tuple())? == u6::new(0b001111))
     }
 
-    fn consume_operand(&mut self, operand: Register) -> u6 {
+    fn consume_operand(&mut self, operand: Register) -> Result<u6, ExecutionError> {
         match operand {
-            Register::A => self.a,
-            Register::B => self.b,
-            Register::C => self.c,
+            Register::A => Ok(self.a),
+            Register::B => Ok(self.b),
+            Register::C => Ok(self.c),
             Register::Immediate => {
+                self.cycles += 1;
                 self.program_counter.increment();
                 self.memory.read(self.program_counter.as_tuple())
             }
         }
     }
 
-    pub fn consume_instruction(&mut self) {
-        let raw_value = self.memory.read(self.program_counter.as_tuple());
+    /// Execute one instruction; on a fault the program counter is left on the faulting instruction.
+    pub fn consume_instruction(&mut self) -> Result<(), ExecutionError> {
+        self.check()?;
+        let start = self.program_counter.as_tuple();
+        let registers = (self.a, self.b, self.c);
+        let cycles = self.cycles;
+        let interrupt_return = self.interrupt_return;
+
+        let mut step = self.execute_instruction().inspect_err(|_| {
+            self.program_counter.set(start);
+            self.cycles = cycles;
+        })?;
+
+        if let Some(history) = &mut self.history {
+            history.push(Delta {
+                program_counter: start,
+                registers,
+                cycles,
+                interrupt_return,
+                last_instruction: self.last_instruction,
+                replaced: std::mem::take(&mut step.replaced),
+            });
+        }
+        self.last_instruction = Some(start);
+        if let Some(profile) = &mut self.profile {
+            profile.record(tuple_as_usize(start), self.cycles - cycles);
+        }
+        if let Some(trace) = &mut self.trace {
+            trace.record(TraceEntry::new(
+                start,
+                step,
+                registers,
+                (self.a, self.b, self.c),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Undo the most recent step or interrupt, returning false once the history is exhausted.
+    pub fn step_back(&mut self) -> bool {
+        let Some(delta) = self.history.as_mut().and_then(History::pop) else {
+            return false;
+        };
+
+        for (address, cell) in delta.replaced.into_iter().rev() {
+            self.memory.restore(address, cell);
+        }
+        (self.a, self.b, self.c) = delta.registers;
+        self.cycles = delta.cycles;
+        self.interrupt_return = delta.interrupt_return;
+        self.last_instruction = delta.last_instruction;
+        self.program_counter.set(delta.program_counter);
+        true
+    }
+
+    fn execute_instruction(&mut self) -> Result<Step, ExecutionError> {
+        let raw_value = self.memory.read(self.program_counter.as_tuple())?;
+        self.cycles += 1;
 
         // TODO add special instructions
         match raw_value.value() {
             0b001100 /* Nop */ => {
                 self.program_counter.increment();
-                return;
+                return Ok(Step::special(raw_value));
+            }
+            0b001101 | 0b001110 if self.strict => {
+                return Err(ExecutionError::IllegalOpcode(raw_value))
+            }
+            0b001101 /* Trp */ => {
+                self.program_counter.increment();
+                let mut step = Step::special(raw_value);
+                if let Some(mut trap_handler) = self.trap_handler.take() {
+                    // The handler may write anywhere, so compare all of memory to undo it.
+                    let before = self.history.is_some().then(|| self.memory.cells());
+                    trap_handler(self);
+                    self.trap_handler = Some(trap_handler);
+                    if let Some(before) = before {
+                        step.replaced = self.memory.changed_cells(&before);
+                    }
+                }
+                return Ok(step);
+            }
+            0b001110 /* Irt */ => {
+                let address = self
+                    .interrupt_return
+                    .take()
+                    .ok_or(ExecutionError::ReturnWithoutInterrupt)?;
+                self.program_counter.set(address);
+                return Ok(Step::special(raw_value));
             }
-            0b001101 => unimplemented!(),
-            0b001110 => unimplemented!(),
             0b001111 /* Hlt */ => {
                 self.program_counter.increment();
-                return;
+                return Ok(Step::special(raw_value));
             },
             _ => (),
         }
 
         let instruction = Instruction::new_with_raw_value(raw_value);
 
-        let operand_one = self.consume_operand(instruction.one());
-        let operand_two = self.consume_operand(instruction.two());
+        let operand_one = self.consume_operand(instruction.one())?;
+        let operand_two = self.consume_operand(instruction.two())?;
         self.program_counter.increment();
 
+        let mut write = None;
+        let mut replaced = Vec::new();
         match instruction.operation() {
             Operation::Nor => {
                 let new_value = !(operand_one | operand_two);
@@ -83,8 +287,26 @@ impl InteractiveState {
                 }
             }
             Operation::Pc => self.program_counter.set((operand_one, operand_two)),
-            Operation::Load => self.c = self.memory.read((operand_one, operand_two)),
-            Operation::Store => self.memory.write((operand_one, operand_two), self.c),
+            Operation::Load => self.c = self.memory.read((operand_one, operand_two))?,
+            Operation::Store => {
+                let address = (operand_one, operand_two);
+                // An unmapped word has no old value, which only `AllowAsRam` lets through.
+                let old = self.memory.read(address).ok();
+                replaced.push((address, self.memory.cell(address)));
+                self.memory.write(address, self.c)?;
+                write = Some(MemoryWrite {
+                    address,
+                    old,
+                    new: self.c,
+                });
+            }
         }
+
+        Ok(Step {
+            raw_value,
+            operands: Some((operand_one, operand_two)),
+            write,
+            replaced,
+        })
     }
 }
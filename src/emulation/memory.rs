@@ -1,14 +1,30 @@
 use arbitrary_int::u6;
 
-use crate::utils::tuple_as_usize;
+use crate::utils::{tuple_as_usize, usize_as_tuple};
 
-use super::program_counter::ProgramCounter;
+use super::{program_counter::ProgramCounter, ExecutionError};
 
 const RAM_SIZE: usize = 3902;
+const ROM_SIZE: usize = 4096 - RAM_SIZE;
+
+/// What a write to the read-only range `0xF3E..=0xFFF` does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RomPolicy {
+    /// Discard the write, like hardware without a write enable
+    Ignore,
+    /// Stop with an `ExecutionError`
+    #[default]
+    Fault,
+    /// Store the value; later reads return it instead of the ROM
+    AllowAsRam,
+}
 
 pub struct Memory {
     pub program_counter: ProgramCounter,
     pub ram: [u6; RAM_SIZE],
+    pub rom_policy: RomPolicy,
+    /// Values written to ROM addresses under `RomPolicy::AllowAsRam`.
+    rom_overlay: [Option<u6>; ROM_SIZE],
 }
 
 impl Memory {
@@ -16,34 +32,167 @@ impl Memory {
         Self {
             program_counter,
             ram: [u6::default(); RAM_SIZE],
+            rom_policy: RomPolicy::default(),
+            rom_overlay: [None; ROM_SIZE],
         }
     }
 
-    pub fn store_array(&mut self, offset: usize, machine_code: &[u6]) {
-        self.ram[offset..offset + machine_code.len()].copy_from_slice(&machine_code);
+    pub fn store_array(
+        &mut self,
+        offset: usize,
+        machine_code: &[u6],
+    ) -> Result<(), ExecutionError> {
+        let destination = offset
+            .checked_add(machine_code.len())
+            .and_then(|end| self.ram.get_mut(offset..end))
+            .ok_or(ExecutionError::OutOfRangeLoad {
+                offset,
+                length: machine_code.len(),
+            })?;
+
+        destination.copy_from_slice(machine_code);
+        Ok(())
     }
 
-    pub fn read(&self, address: (u6, u6)) -> u6 {
+    /// Fails for the unmapped range `0xF40..=0xF7F`, unless it was written as RAM.
+    pub fn read(&self, address: (u6, u6)) -> Result<u6, ExecutionError> {
         let as_usize = tuple_as_usize(address);
 
-        match as_usize {
+        Ok(match as_usize {
             0x000..=0xF3D => self.ram[as_usize],
+            0xF3E..=0xFFF if self.rom_overlay[as_usize - RAM_SIZE].is_some() => {
+                self.rom_overlay[as_usize - RAM_SIZE].unwrap()
+            }
             0xF3E => self.program_counter.as_tuple().0,
             0xF3F => self.program_counter.as_tuple().1,
             0xF80..=0xFBF => address.1.rotate_left(1),
             0xFC0..=0xFFF => address.1.rotate_right(1),
-            _ => unreachable!(),
+            _ => return Err(ExecutionError::UnmappedRead(address)),
+        })
+    }
+
+    /// The word stored at `address` rather than read from it; `None` for ROM not written as RAM.
+    pub fn cell(&self, address: (u6, u6)) -> Option<u6> {
+        let as_usize = tuple_as_usize(address);
+
+        match as_usize {
+            0x000..=0xF3D => Some(self.ram[as_usize]),
+            _ => self.rom_overlay[as_usize - RAM_SIZE],
         }
     }
 
-    pub fn write(&mut self, address: (u6, u6), value: u6) {
+    /// Put back a value returned by `cell`, whatever the ROM policy.
+    pub fn restore(&mut self, address: (u6, u6), cell: Option<u6>) {
+        let as_usize = tuple_as_usize(address);
+
+        match as_usize {
+            0x000..=0xF3D => self.ram[as_usize] = cell.expect("RAM always holds a word"),
+            _ => self.rom_overlay[as_usize - RAM_SIZE] = cell,
+        }
+    }
+
+    /// The `cell` of every address, in order.
+    pub fn cells(&self) -> Vec<Option<u6>> {
+        (0..RAM_SIZE + ROM_SIZE)
+            .map(|index| self.cell(usize_as_tuple(index)))
+            .collect()
+    }
+
+    /// The addresses whose cell differs from `before`, paired with the old cell.
+    pub fn changed_cells(&self, before: &[Option<u6>]) -> Vec<((u6, u6), Option<u6>)> {
+        before
+            .iter()
+            .enumerate()
+            .map(|(index, cell)| (usize_as_tuple(index), *cell))
+            .filter(|(address, cell)| self.cell(*address) != *cell)
+            .collect()
+    }
+
+    pub fn write(&mut self, address: (u6, u6), value: u6) -> Result<(), ExecutionError> {
         let as_usize = tuple_as_usize(address);
 
         match as_usize {
             0x000..=0xF3D => self.ram[as_usize] = value,
             // TODO I don't know what to use this for, but I am not letting this many addresses go to waste
-            0xF3E..=0xFFF => todo!(),
+            0xF3E..=0xFFF => match self.rom_policy {
+                RomPolicy::Ignore => (),
+                RomPolicy::Fault => return Err(ExecutionError::WriteToRom(address)),
+                RomPolicy::AllowAsRam => self.rom_overlay[as_usize - RAM_SIZE] = Some(value),
+            },
             _ => unreachable!(),
         }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use arbitrary_int::u6;
+
+    use super::{Memory, RomPolicy, RAM_SIZE};
+    use crate::emulation::{program_counter::ProgramCounter, ExecutionError};
+
+    #[test]
+    fn test_store_array_bounds() {
+        let mut memory = Memory::new(ProgramCounter::default());
+        let machine_code = [u6::new(1); 4];
+
+        assert_eq!(memory.store_array(RAM_SIZE - 4, &machine_code), Ok(()));
+        assert_eq!(memory.ram[RAM_SIZE - 1], u6::new(1));
+
+        assert_eq!(
+            memory.store_array(RAM_SIZE - 3, &machine_code),
+            Err(ExecutionError::OutOfRangeLoad {
+                offset: RAM_SIZE - 3,
+                length: 4
+            })
+        );
+        assert!(memory.store_array(usize::MAX, &machine_code).is_err());
+    }
+
+    #[test]
+    fn test_write_to_rom() {
+        let mut memory = Memory::new(ProgramCounter::default());
+        let address = (u6::new(0x3C), u6::new(0x3E));
+
+        assert_eq!(
+            memory.write(address, u6::new(1)),
+            Err(ExecutionError::WriteToRom(address))
+        );
+        assert_eq!(memory.write((u6::new(0), u6::new(0)), u6::new(1)), Ok(()));
+    }
+
+    #[test]
+    fn test_rom_policy() {
+        let mut memory = Memory::new(ProgramCounter::default());
+        // An entry in the left rotate lookup table
+        let address = (u6::new(0x3E), u6::new(0x01));
+
+        memory.rom_policy = RomPolicy::Ignore;
+        assert_eq!(memory.write(address, u6::new(7)), Ok(()));
+        assert_eq!(memory.read(address), Ok(u6::new(0b000010)));
+
+        memory.rom_policy = RomPolicy::AllowAsRam;
+        assert_eq!(memory.write(address, u6::new(7)), Ok(()));
+        assert_eq!(memory.read(address), Ok(u6::new(7)));
+        assert_eq!(
+            memory.read((u6::new(0x3E), u6::new(0x02))),
+            Ok(u6::new(0b000100))
+        );
+    }
+
+    #[test]
+    fn test_read_unmapped() {
+        let mut memory = Memory::new(ProgramCounter::default());
+        let address = (u6::new(0x3D), u6::new(0x00));
+
+        assert_eq!(
+            memory.read(address),
+            Err(ExecutionError::UnmappedRead(address))
+        );
+
+        memory.rom_policy = RomPolicy::AllowAsRam;
+        memory.write(address, u6::new(7)).unwrap();
+        assert_eq!(memory.read(address), Ok(u6::new(7)));
     }
 }
@@ -1,5 +1,16 @@
+mod coverage;
+mod execution_error;
+mod history;
 mod interactive_state;
 mod memory;
+mod profile;
 mod program_counter;
+mod trace;
 
-pub use interactive_state::InteractiveState;
+pub use coverage::lcov;
+pub use execution_error::ExecutionError;
+pub use history::History;
+pub use interactive_state::{InteractiveState, TrapHandler};
+pub use memory::RomPolicy;
+pub use profile::Profile;
+pub use trace::Trace;
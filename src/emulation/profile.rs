@@ -0,0 +1,135 @@
+use std::{collections::HashMap, fmt::Write, sync::Arc};
+
+use arbitrary_int::u12;
+
+use crate::compilation::debug_info::DebugInfo;
+
+/// Name used for code before the first label.
+const UNLABELED: &str = "(unlabeled)";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Count {
+    pub executions: u64,
+    pub cycles: u64,
+}
+
+impl Count {
+    fn add(&mut self, other: Count) {
+        self.executions += other.executions;
+        self.cycles += other.cycles;
+    }
+}
+
+/// How often the instruction at each address was executed and how many cycles it took.
+pub struct Profile {
+    counts: Vec<Count>,
+}
+
+impl Profile {
+    pub fn new() -> Self {
+        Self {
+            counts: vec![Count::default(); 4096],
+        }
+    }
+
+    pub fn record(&mut self, address: usize, cycles: u64) {
+        let count = &mut self.counts[address];
+        count.executions += 1;
+        count.cycles += cycles;
+    }
+
+    pub fn executions(&self, address: usize) -> u64 {
+        self.counts[address].executions
+    }
+
+    /// Addresses that were executed at least once, in address order.
+    pub fn addresses(&self) -> impl Iterator<Item = (usize, Count)> + '_ {
+        self.counts
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| count.executions > 0)
+            .map(|(address, count)| (address, *count))
+    }
+
+    /// Counts summed under the closest label at or before each address, most cycles first.
+    pub fn by_label(&self, symbol_table: &HashMap<Arc<str>, u12>) -> Vec<(Arc<str>, Count)> {
+        let labels = Labels::new(symbol_table);
+        let mut totals: HashMap<Arc<str>, Count> = HashMap::new();
+        for (address, count) in self.addresses() {
+            totals
+                .entry(labels.enclosing(address))
+                .or_default()
+                .add(count);
+        }
+        sorted(totals)
+    }
+
+    /// Counts summed per source line, most cycles first; code without a statement is skipped.
+    pub fn by_line(&self, debug_info: &DebugInfo) -> Vec<(usize, Count)> {
+        let mut totals: HashMap<usize, Count> = HashMap::new();
+        for (address, count) in self.addresses() {
+            if let Some(statement) = debug_info.statement_at(u12::new(address as u16)) {
+                totals.entry(statement.line).or_default().add(count);
+            }
+        }
+        sorted(totals)
+    }
+
+    /// Cycles as folded stacks (`LABEL;.LOCAL;line 4 12`) for flamegraph tools.
+    pub fn folded(&self, symbol_table: &HashMap<Arc<str>, u12>, debug_info: &DebugInfo) -> String {
+        let labels = Labels::new(symbol_table);
+        let mut totals: HashMap<String, u64> = HashMap::new();
+        for (address, count) in self.addresses() {
+            let label = labels.enclosing(address);
+            // A local label is nested under the global label it is scoped to.
+            let mut stack = match label.split_once('.') {
+                Some((scope, local)) => format!("{scope};.{local}"),
+                None => label.to_string(),
+            };
+            match debug_info.statement_at(u12::new(address as u16)) {
+                Some(statement) => write!(stack, ";line {}", statement.line).unwrap(),
+                None => write!(stack, ";{address:03X}").unwrap(),
+            }
+            *totals.entry(stack).or_default() += count.cycles;
+        }
+
+        let mut stacks: Vec<_> = totals.into_iter().collect();
+        stacks.sort();
+        stacks
+            .into_iter()
+            .map(|(stack, cycles)| format!("{stack} {cycles}\n"))
+            .collect()
+    }
+}
+
+fn sorted<T: Ord>(totals: HashMap<T, Count>) -> Vec<(T, Count)> {
+    let mut totals: Vec<_> = totals.into_iter().collect();
+    totals.sort_by(|(a, a_count), (b, b_count)| b_count.cycles.cmp(&a_count.cycles).then(a.cmp(b)));
+    totals
+}
+
+/// Labels written in the source, ordered so a local label wins over a global one at the same address.
+struct Labels(Vec<(usize, bool, Arc<str>)>);
+
+impl Labels {
+    fn new(symbol_table: &HashMap<Arc<str>, u12>) -> Self {
+        let mut labels: Vec<_> = symbol_table
+            .iter()
+            // Generated and anonymous labels mean nothing to the reader of a report.
+            .filter(|(label, _)| !label.starts_with(['#', '+', '-']))
+            .map(|(label, address)| (address.value() as usize, label.contains('.'), label.clone()))
+            .collect();
+        labels.sort();
+        Self(labels)
+    }
+
+    fn enclosing(&self, address: usize) -> Arc<str> {
+        match self
+            .0
+            .partition_point(|(label_address, ..)| *label_address <= address)
+        {
+            0 => Arc::from(UNLABELED),
+            index => self.0[index - 1].2.clone(),
+        }
+    }
+}
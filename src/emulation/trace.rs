@@ -0,0 +1,138 @@
+use std::{collections::VecDeque, fmt, io::Write};
+
+use arbitrary_int::u6;
+
+use crate::{
+    disassembler::disassemble,
+    instruction::{Instruction, Register},
+    utils::tuple_as_usize,
+};
+
+use super::interactive_state::Step;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MemoryWrite {
+    pub address: (u6, u6),
+    /// `None` when nothing was mapped at the address before the write.
+    pub old: Option<u6>,
+    pub new: u6,
+}
+
+/// One executed instruction and everything it changed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceEntry {
+    pub program_counter: (u6, u6),
+    pub raw_value: u6,
+    /// The values of both operands, absent for `NOP`, `TRP`, `IRT` and `HLT`.
+    pub operands: Option<(u6, u6)>,
+    /// Registers whose value changed as `(name, old, new)`.
+    pub registers: Vec<(char, u6, u6)>,
+    pub write: Option<MemoryWrite>,
+}
+
+impl TraceEntry {
+    pub fn new(
+        program_counter: (u6, u6),
+        step: Step,
+        before: (u6, u6, u6),
+        after: (u6, u6, u6),
+    ) -> Self {
+        let registers = [
+            ('A', before.0, after.0),
+            ('B', before.1, after.1),
+            ('C', before.2, after.2),
+        ]
+        .into_iter()
+        .filter(|(_, old, new)| old != new)
+        .collect();
+
+        Self {
+            program_counter,
+            raw_value: step.raw_value,
+            operands: step.operands,
+            registers,
+            write: step.write,
+        }
+    }
+
+    /// The instruction followed by its immediates, as they were fetched.
+    pub fn words(&self) -> Vec<u6> {
+        let mut words = vec![self.raw_value];
+        if let Some((one, two)) = self.operands {
+            let instruction = Instruction::new_with_raw_value(self.raw_value);
+            for (register, value) in [(instruction.one(), one), (instruction.two(), two)] {
+                if register == Register::Immediate {
+                    words.push(value);
+                }
+            }
+        }
+        words
+    }
+}
+
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let address = tuple_as_usize(self.program_counter);
+        let text = &disassemble(&self.words(), address)[0].text;
+        write!(f, "{address:0>3X}: {text:<16}")?;
+
+        if let Some((one, two)) = self.operands {
+            write!(f, " ({one:0>6b}, {two:0>6b})")?;
+        }
+        for (name, old, new) in &self.registers {
+            write!(f, " {name}: {old:0>6b} -> {new:0>6b}")?;
+        }
+        if let Some(write) = &self.write {
+            write!(f, " [{:0>3X}]: ", tuple_as_usize(write.address))?;
+            match write.old {
+                Some(old) => write!(f, "{old:0>6b}")?,
+                None => write!(f, "------")?,
+            }
+            write!(f, " -> {:0>6b}", write.new)?;
+        }
+        Ok(())
+    }
+}
+
+/// Keeps the most recent entries and optionally writes every entry to a file.
+pub struct Trace {
+    entries: VecDeque<TraceEntry>,
+    capacity: usize,
+    writer: Option<Box<dyn Write>>,
+}
+
+impl Trace {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+            writer: None,
+        }
+    }
+
+    pub fn with_writer(mut self, writer: impl Write + 'static) -> Self {
+        self.writer = Some(Box::new(writer));
+        self
+    }
+
+    pub fn record(&mut self, entry: TraceEntry) {
+        if let Some(writer) = &mut self.writer {
+            // A failing trace file should not stop the program being traced.
+            let _ = writeln!(writer, "{entry}");
+        }
+
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        if self.capacity > 0 {
+            self.entries.push_back(entry);
+        }
+    }
+
+    /// The last `count` entries, oldest first.
+    pub fn last(&self, count: usize) -> impl Iterator<Item = &TraceEntry> {
+        self.entries
+            .iter()
+            .skip(self.entries.len().saturating_sub(count))
+    }
+}
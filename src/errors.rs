@@ -1,12 +1,46 @@
 use std::io::Error as IoError;
 
+use crate::{
+    compilation::object::{LinkError, ObjectError},
+    emulation::ExecutionError,
+    image::ImageError,
+};
+
 #[derive(Debug)]
 pub enum Error {
-    IoError(IoError),
+    Io(IoError),
+    Object(ObjectError),
+    Link(LinkError),
+    Image(ImageError),
+    Execution(ExecutionError),
 }
 
 impl From<IoError> for Error {
     fn from(value: IoError) -> Self {
-        Self::IoError(value)
+        Self::Io(value)
+    }
+}
+
+impl From<ObjectError> for Error {
+    fn from(value: ObjectError) -> Self {
+        Self::Object(value)
+    }
+}
+
+impl From<LinkError> for Error {
+    fn from(value: LinkError) -> Self {
+        Self::Link(value)
+    }
+}
+
+impl From<ImageError> for Error {
+    fn from(value: ImageError) -> Self {
+        Self::Image(value)
+    }
+}
+
+impl From<ExecutionError> for Error {
+    fn from(value: ExecutionError) -> Self {
+        Self::Execution(value)
     }
 }
@@ -0,0 +1,165 @@
+use crate::compilation::{
+    lexer::Cursor,
+    tokens::{Keyword, Token, TokenKind},
+};
+
+const INDENT: &str = "    ";
+
+/// One source line: its block depth, the statement and any comment after it.
+struct Line<'a> {
+    depth: usize,
+    code: String,
+    comment: Option<&'a str>,
+}
+
+impl Line<'_> {
+    fn width(&self) -> usize {
+        self.depth * INDENT.len() + self.code.chars().count()
+    }
+}
+
+/// Reformat DCL source: keywords and registers are uppercased, `IF`/`WHILE` blocks are indented,
+/// runs of blank lines collapse to one and trailing comments in a block of statements line up.
+///
+/// Tokens keep their original text and adjacency (`LAB ++` is not `LAB + +`), comments are copied
+/// as written.
+pub fn format(source: &str) -> String {
+    // ASCII uppercasing keeps the spans valid in the original source.
+    let upper = source.to_ascii_uppercase();
+    let tokens: Vec<Token> = Cursor::new(&upper).tokenize().collect();
+
+    let mut lines: Vec<Option<Line>> = Vec::new();
+    let mut depth = 0usize;
+    for tokens in tokens.split(|token| token.kind == TokenKind::NewLine) {
+        let (comment, code) = match tokens.split_last() {
+            Some((last, code)) if last.kind == TokenKind::LineComment => {
+                let comment = &source[last.span.start..last.span.end];
+                (Some(comment.trim_end_matches('\r')), code)
+            }
+            _ => (None, tokens),
+        };
+        if code.is_empty() && comment.is_none() {
+            lines.push(None);
+            continue;
+        }
+
+        let mut line_depth = depth;
+        match code.first().map(|token| &token.kind) {
+            Some(TokenKind::Keyword(Keyword::If | Keyword::While)) => depth += 1,
+            Some(TokenKind::Keyword(Keyword::Else)) => line_depth = depth.saturating_sub(1),
+            Some(TokenKind::Keyword(Keyword::End)) => {
+                depth = depth.saturating_sub(1);
+                line_depth = depth;
+            }
+            _ => (),
+        }
+
+        let mut text = String::new();
+        for (index, token) in code.iter().enumerate() {
+            if index > 0 && code[index - 1].span.end < token.span.start {
+                text.push(' ');
+            }
+            let range = token.span.start..token.span.end;
+            text.push_str(match token.kind {
+                TokenKind::Keyword(_) | TokenKind::Register(_) => &upper[range],
+                _ => &source[range],
+            });
+        }
+        lines.push(Some(Line {
+            depth: line_depth,
+            code: text,
+            comment,
+        }));
+    }
+
+    let mut output = String::new();
+    let mut blank = false;
+    let mut index = 0;
+    while index < lines.len() {
+        let Some(line) = &lines[index] else {
+            blank = !output.is_empty();
+            index += 1;
+            continue;
+        };
+        if blank {
+            output.push('\n');
+            blank = false;
+        }
+
+        // Statements up to the next blank or comment-only line share a comment column.
+        let block = lines[index..]
+            .iter()
+            .take_while(|line| line.as_ref().is_some_and(|line| !line.code.is_empty()))
+            .flatten()
+            .collect::<Vec<_>>();
+        if block.is_empty() {
+            output.push_str(&INDENT.repeat(line.depth));
+            output.push_str(line.comment.unwrap_or_default());
+            output.push('\n');
+            index += 1;
+            continue;
+        }
+
+        let column = block
+            .iter()
+            .filter(|line| line.comment.is_some())
+            .map(|line| line.width())
+            .max()
+            .unwrap_or_default();
+        for line in &block {
+            output.push_str(&INDENT.repeat(line.depth));
+            output.push_str(&line.code);
+            if let Some(comment) = line.comment {
+                output.push_str(&" ".repeat(column - line.width() + 1));
+                output.push_str(comment);
+            }
+            output.push('\n');
+        }
+        index += block.len();
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format;
+
+    #[test]
+    fn test_format_casing_and_spacing() {
+        let source = "lab main\n  mov   a b\nlab ++\nPC (main:1 + 1) main:0\nset 'a'\n";
+        assert_eq!(
+            format(source),
+            "LAB main\nMOV A B\nLAB ++\nPC (main:1 + 1) main:0\nSET 'a'\n"
+        );
+    }
+
+    #[test]
+    fn test_format_blocks() {
+        let source =
+            "if [a == b]\nnop\n      else\nwhile [c != 0]\nsub c 1\n# inner\nend\nend\nhlt";
+        assert_eq!(
+            format(source),
+            "IF [A == B]\n    NOP\nELSE\n    WHILE [C != 0]\n        SUB C 1\n        # inner\n    END\nEND\nHLT\n"
+        );
+    }
+
+    #[test]
+    fn test_format_comments_and_blank_lines() {
+        let source =
+            "\n\n# HEADER  kept   as is\nnop # one\nmov a b   #two\n\n\n\nnot c #  three\n\n";
+        assert_eq!(
+            format(source),
+            "# HEADER  kept   as is\nNOP     # one\nMOV A B #two\n\nNOT C #  three\n"
+        );
+    }
+
+    #[test]
+    fn test_format_idempotent() {
+        let source = include_str!("../examples/fibonacci.dcl");
+        assert_eq!(format(source), source);
+        assert_eq!(
+            format(&format("if [a==1]\n pc x # go\nend")),
+            format("if [a==1]\n pc x # go\nend")
+        );
+    }
+}
@@ -0,0 +1,495 @@
+use std::{
+    collections::BTreeSet,
+    fmt::Write as _,
+    io::{self, BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+};
+
+use arbitrary_int::u6;
+
+use crate::{
+    emulation::{ExecutionError, InteractiveState},
+    utils::{tuple_as_usize, usize_as_tuple},
+};
+
+/// The registers in the order `g` sends them; every word is a byte and the PC is two.
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.dianac.core">
+    <reg name="a" bitsize="8" type="uint8" regnum="0"/>
+    <reg name="b" bitsize="8" type="uint8"/>
+    <reg name="c" bitsize="8" type="uint8"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+  </feature>
+</target>
+"#;
+
+const ADDRESS_SPACE: usize = 4096;
+
+/// How many instructions run between checks for a `Ctrl-C` from GDB.
+const INTERRUPT_POLL: usize = 1024;
+
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+const SIGABRT: u8 = 6;
+const SIGSEGV: u8 = 11;
+
+/// What the connection should do after a packet.
+#[derive(Debug, PartialEq)]
+enum Action {
+    Reply(String),
+    Resume { step: bool },
+    Detach,
+}
+
+/// Answers GDB packets by inspecting and running the emulator.
+struct Stub<'a> {
+    state: &'a mut InteractiveState,
+    breakpoints: BTreeSet<usize>,
+}
+
+impl<'a> Stub<'a> {
+    fn new(state: &'a mut InteractiveState) -> Self {
+        Self {
+            state,
+            breakpoints: BTreeSet::new(),
+        }
+    }
+
+    fn handle(&mut self, packet: &str) -> Action {
+        match packet.chars().next() {
+            Some(command @ ('c' | 's')) => {
+                if let Some(address) =
+                    parse_hex(&packet[1..]).filter(|address| *address < ADDRESS_SPACE)
+                {
+                    self.state.program_counter.set(usize_as_tuple(address));
+                }
+                Action::Resume {
+                    step: command == 's',
+                }
+            }
+            Some('D' | 'k') => Action::Detach,
+            // An empty reply tells GDB the packet isn't supported.
+            _ => Action::Reply(self.reply(packet).unwrap_or_default()),
+        }
+    }
+
+    fn reply(&mut self, packet: &str) -> Option<String> {
+        let reply = match packet {
+            "?" => stop_reply(SIGTRAP),
+            "g" => hex(&(0..4)
+                .flat_map(|index| self.register(index).unwrap())
+                .collect::<Vec<_>>()),
+            "qAttached" => "1".to_string(),
+            "qC" => "QC1".to_string(),
+            "qfThreadInfo" => "m1".to_string(),
+            "qsThreadInfo" => "l".to_string(),
+            _ if packet.starts_with("qSupported") => {
+                "PacketSize=1000;qXfer:features:read+".to_string()
+            }
+            _ if packet.starts_with('H') => "OK".to_string(),
+            _ => {
+                let (command, arguments) = (packet.get(..1)?, packet.get(1..)?);
+                let result = match command {
+                    "G" => self.write_registers(arguments),
+                    "p" => parse_hex(arguments)
+                        .and_then(|index| self.register(index))
+                        .map(|bytes| hex(&bytes)),
+                    "P" => self.write_register(arguments),
+                    "m" => self.read_memory(arguments),
+                    "M" => self.write_memory(arguments),
+                    "Z" | "z" => return self.breakpoint(command == "Z", arguments),
+                    "q" => return target_xml(arguments),
+                    _ => return None,
+                };
+                result.unwrap_or_else(|| "E01".to_string())
+            }
+        };
+        Some(reply)
+    }
+
+    /// Little-endian bytes of register `index`: A, B, C, then the PC.
+    fn register(&self, index: usize) -> Option<Vec<u8>> {
+        match index {
+            0 => Some(vec![self.state.a.value()]),
+            1 => Some(vec![self.state.b.value()]),
+            2 => Some(vec![self.state.c.value()]),
+            3 => Some(
+                (tuple_as_usize(self.state.program_counter.as_tuple()) as u16)
+                    .to_le_bytes()
+                    .to_vec(),
+            ),
+            _ => None,
+        }
+    }
+
+    fn set_register(&mut self, index: usize, bytes: &[u8]) -> Option<()> {
+        match (index, bytes) {
+            (0, [value]) => self.state.a = u6::try_new(*value).ok()?,
+            (1, [value]) => self.state.b = u6::try_new(*value).ok()?,
+            (2, [value]) => self.state.c = u6::try_new(*value).ok()?,
+            (3, [low, high]) => {
+                let address = u16::from_le_bytes([*low, *high]) as usize;
+                if address >= ADDRESS_SPACE {
+                    return None;
+                }
+                self.state.program_counter.set(usize_as_tuple(address));
+            }
+            _ => return None,
+        }
+        Some(())
+    }
+
+    fn write_registers(&mut self, arguments: &str) -> Option<String> {
+        let bytes = decode_hex(arguments)?;
+        let [a, b, c, low, high] = bytes[..] else {
+            return None;
+        };
+        // Check everything before changing anything.
+        [a, b, c]
+            .iter()
+            .try_for_each(|value| u6::try_new(*value).map(|_| ()).ok())?;
+        if u16::from_le_bytes([low, high]) as usize >= ADDRESS_SPACE {
+            return None;
+        }
+        for (index, bytes) in [&[a][..], &[b], &[c], &[low, high]].into_iter().enumerate() {
+            self.set_register(index, bytes)?;
+        }
+        Some("OK".to_string())
+    }
+
+    fn write_register(&mut self, arguments: &str) -> Option<String> {
+        let (index, value) = arguments.split_once('=')?;
+        self.set_register(parse_hex(index)?, &decode_hex(value)?)?;
+        Some("OK".to_string())
+    }
+
+    /// Reads stop early at the end of the address space or the unmapped range, like a partial read.
+    fn read_memory(&self, arguments: &str) -> Option<String> {
+        let (address, length) = arguments.split_once(',')?;
+        let (address, length) = (parse_hex(address)?, parse_hex(length)?);
+        let words: Vec<u8> = (address..address.saturating_add(length))
+            .map_while(|address| {
+                (address < ADDRESS_SPACE)
+                    .then(|| self.state.memory.read(usize_as_tuple(address)).ok())
+                    .flatten()
+            })
+            .map(|word| word.value())
+            .collect();
+        (!words.is_empty() || length == 0).then(|| hex(&words))
+    }
+
+    fn write_memory(&mut self, arguments: &str) -> Option<String> {
+        let (range, data) = arguments.split_once(':')?;
+        let (address, length) = range.split_once(',')?;
+        let (address, length) = (parse_hex(address)?, parse_hex(length)?);
+        let words = decode_hex(data)?
+            .into_iter()
+            .map(|byte| u6::try_new(byte).ok())
+            .collect::<Option<Vec<_>>>()?;
+        if words.len() != length || address.saturating_add(length) > ADDRESS_SPACE {
+            return None;
+        }
+        for (index, word) in words.into_iter().enumerate() {
+            self.state
+                .memory
+                .write(usize_as_tuple(address + index), word)
+                .ok()?;
+        }
+        Some("OK".to_string())
+    }
+
+    /// Software and hardware breakpoints are the same thing here; watchpoints aren't supported.
+    fn breakpoint(&mut self, insert: bool, arguments: &str) -> Option<String> {
+        let mut fields = arguments.split(',');
+        if !matches!(fields.next(), Some("0" | "1")) {
+            return None;
+        }
+        let Some(address) = fields.next().and_then(parse_hex) else {
+            return Some("E01".to_string());
+        };
+        if insert {
+            self.breakpoints.insert(address);
+        } else {
+            self.breakpoints.remove(&address);
+        }
+        Some("OK".to_string())
+    }
+
+    /// Run until a breakpoint, a fault, `HLT` or `interrupted`; a step always stops after one instruction.
+    fn resume(&mut self, step: bool, mut interrupted: impl FnMut() -> bool) -> String {
+        // `HLT` waits for an interrupt, which GDB has no way to raise.
+        if self.state.is_halt() == Ok(true) {
+            return stop_reply(SIGTRAP);
+        }
+
+        let mut count = 0;
+        loop {
+            if let Err(error) = self.state.consume_instruction() {
+                return stop_reply(match error {
+                    ExecutionError::IllegalOpcode(_) | ExecutionError::ReturnWithoutInterrupt => {
+                        SIGILL
+                    }
+                    ExecutionError::WriteToRom(_)
+                    | ExecutionError::UnmappedRead(_)
+                    | ExecutionError::OutOfRangeLoad { .. } => SIGSEGV,
+                    ExecutionError::FailedCheck { .. } => SIGABRT,
+                });
+            }
+            let program_counter = tuple_as_usize(self.state.program_counter.as_tuple());
+            if step
+                || self.state.is_halt() == Ok(true)
+                || self.breakpoints.contains(&program_counter)
+            {
+                return stop_reply(SIGTRAP);
+            }
+
+            count += 1;
+            if count % INTERRUPT_POLL == 0 && interrupted() {
+                return stop_reply(SIGINT);
+            }
+        }
+    }
+}
+
+/// Wait for GDB on `listener` and serve it until it detaches or disconnects.
+pub fn serve(state: &mut InteractiveState, listener: TcpListener) -> io::Result<()> {
+    let (stream, _) = listener.accept()?;
+    stream.set_nodelay(true)?;
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    let mut stub = Stub::new(state);
+
+    while let Some(packet) = read_packet(&mut reader, &mut writer)? {
+        let reply = match stub.handle(&packet) {
+            Action::Reply(reply) => reply,
+            Action::Resume { step } => stub.resume(step, || interrupted(&mut reader)),
+            Action::Detach => {
+                write_packet(&mut writer, "OK")?;
+                break;
+            }
+        };
+        write_packet(&mut writer, &reply)?;
+    }
+    Ok(())
+}
+
+/// Check, without blocking, for the `Ctrl-C` byte GDB sends to stop a running target.
+fn interrupted(reader: &mut BufReader<TcpStream>) -> bool {
+    if reader.buffer().is_empty() && reader.get_ref().set_nonblocking(true).is_ok() {
+        let _ = reader.fill_buf();
+        let _ = reader.get_ref().set_nonblocking(false);
+    }
+    let interrupted = reader.buffer().first() == Some(&0x03);
+    if interrupted {
+        reader.consume(1);
+    }
+    interrupted
+}
+
+/// Read and acknowledge the next `$data#checksum` packet, or `None` once GDB disconnects.
+fn read_packet(reader: &mut impl BufRead, writer: &mut impl Write) -> io::Result<Option<String>> {
+    loop {
+        let mut byte = [0];
+        if reader.read(&mut byte)? == 0 {
+            return Ok(None);
+        }
+        // Acknowledgements and a stray `Ctrl-C` while stopped need no answer.
+        if byte[0] != b'$' {
+            continue;
+        }
+
+        let mut data = Vec::new();
+        reader.read_until(b'#', &mut data)?;
+        if data.pop() != Some(b'#') {
+            return Ok(None);
+        }
+        let mut checksum = [0; 2];
+        reader.read_exact(&mut checksum)?;
+
+        let expected = std::str::from_utf8(&checksum)
+            .ok()
+            .and_then(|checksum| u8::from_str_radix(checksum, 16).ok());
+        if expected == Some(checksum_of(&data)) {
+            writer.write_all(b"+")?;
+            return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+        }
+        writer.write_all(b"-")?;
+    }
+}
+
+fn write_packet(writer: &mut impl Write, data: &str) -> io::Result<()> {
+    write!(writer, "${data}#{:02x}", checksum_of(data.as_bytes()))?;
+    writer.flush()
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, byte| sum.wrapping_add(*byte))
+}
+
+fn stop_reply(signal: u8) -> String {
+    format!("S{signal:02x}")
+}
+
+/// `qXfer:features:read:target.xml:offset,length`, sent in chunks prefixed `m` (more) or `l` (last).
+fn target_xml(arguments: &str) -> Option<String> {
+    let range = arguments.strip_prefix("Xfer:features:read:")?;
+    let Some(range) = range.strip_prefix("target.xml:") else {
+        return Some("E00".to_string());
+    };
+    let (offset, length) = range.split_once(',')?;
+    let (offset, length) = (parse_hex(offset)?, parse_hex(length)?);
+
+    let start = offset.min(TARGET_XML.len());
+    let end = offset.saturating_add(length).min(TARGET_XML.len());
+    let prefix = if end == TARGET_XML.len() { 'l' } else { 'm' };
+    Some(format!("{prefix}{}", &TARGET_XML[start..end]))
+}
+
+fn parse_hex(text: &str) -> Option<usize> {
+    usize::from_str_radix(text, 16).ok()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut text, byte| {
+        write!(text, "{byte:02x}").unwrap();
+        text
+    })
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(text.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use arbitrary_int::{u12, u6};
+
+    use super::{read_packet, write_packet, Action, Stub};
+    use crate::{compilation::compile_to_binary, emulation::InteractiveState};
+
+    fn state_with(source: &str) -> InteractiveState {
+        let mut state = InteractiveState::new();
+        let binary = compile_to_binary(source, u12::new(0)).binary;
+        state.memory.store_array(0, &binary).unwrap();
+        state
+    }
+
+    fn reply(stub: &mut Stub, packet: &str) -> String {
+        match stub.handle(packet) {
+            Action::Reply(reply) => reply,
+            action => panic!("{action:?}"),
+        }
+    }
+
+    #[test]
+    fn test_packet_framing() {
+        let mut reader = Cursor::new(b"+$g#00$g#67".to_vec());
+        let mut acknowledgements = Vec::new();
+        assert_eq!(
+            read_packet(&mut reader, &mut acknowledgements).unwrap(),
+            Some("g".to_string())
+        );
+        // The first packet has a bad checksum and is asked for again
+        assert_eq!(acknowledgements, b"-+");
+        assert_eq!(
+            read_packet(&mut reader, &mut acknowledgements).unwrap(),
+            None
+        );
+
+        let mut written = Vec::new();
+        write_packet(&mut written, "OK").unwrap();
+        assert_eq!(written, b"$OK#9a");
+    }
+
+    #[test]
+    fn test_registers() {
+        let mut state = InteractiveState::new();
+        state.a = u6::new(5);
+        state.c = u6::new(63);
+        state.program_counter.set((u6::new(1), u6::new(2)));
+        let mut stub = Stub::new(&mut state);
+
+        assert_eq!(reply(&mut stub, "g"), "05003f4200");
+        assert_eq!(reply(&mut stub, "p3"), "4200");
+        assert_eq!(reply(&mut stub, "P1=07"), "OK");
+        assert_eq!(reply(&mut stub, "P1=40"), "E01");
+        assert_eq!(reply(&mut stub, "G0102030010"), "E01");
+        assert_eq!(reply(&mut stub, "G0102030001"), "OK");
+        assert_eq!(reply(&mut stub, "g"), "0102030001");
+    }
+
+    #[test]
+    fn test_memory() {
+        let mut state = state_with("SET 1\nSET 2\nSET 63");
+        let mut stub = Stub::new(&mut state);
+
+        assert_eq!(reply(&mut stub, "m0,3"), "01023f");
+        assert_eq!(reply(&mut stub, "mf3f,3"), "00");
+        assert_eq!(reply(&mut stub, "mf40,1"), "E01");
+        assert_eq!(reply(&mut stub, "M1,2:0a0b"), "OK");
+        assert_eq!(reply(&mut stub, "M1,1:40"), "E01");
+        assert_eq!(reply(&mut stub, "Mf80,1:01"), "E01");
+        assert_eq!(reply(&mut stub, "m0,3"), "010a0b");
+    }
+
+    #[test]
+    fn test_breakpoints_and_steps() {
+        let mut state = state_with("NOP\nNOR A 1\nNOP\nHLT");
+        let mut stub = Stub::new(&mut state);
+
+        assert_eq!(reply(&mut stub, "Z0,3,1"), "OK");
+        assert_eq!(stub.handle("c"), Action::Resume { step: false });
+        assert_eq!(stub.resume(false, || false), "S05");
+        assert_eq!(reply(&mut stub, "p3"), "0300");
+
+        assert_eq!(stub.resume(true, || false), "S05");
+        assert_eq!(reply(&mut stub, "p3"), "0400");
+        // Halted, so neither continuing nor stepping moves on
+        assert_eq!(stub.resume(false, || false), "S05");
+        assert_eq!(reply(&mut stub, "p3"), "0400");
+
+        assert_eq!(reply(&mut stub, "z0,3,1"), "OK");
+        assert_eq!(stub.handle("s0"), Action::Resume { step: true });
+        assert_eq!(stub.resume(false, || false), "S05");
+        assert_eq!(reply(&mut stub, "p3"), "0400");
+        assert_eq!(reply(&mut stub, "Z2,3,1"), "");
+    }
+
+    #[test]
+    fn test_interrupt_and_fault() {
+        let mut state = state_with("PC 0 0");
+        let mut stub = Stub::new(&mut state);
+        assert_eq!(stub.resume(false, || true), "S02");
+
+        let mut state = state_with("STO 0x3C 0x3E\nHLT");
+        let mut stub = Stub::new(&mut state);
+        assert_eq!(stub.resume(false, || false), "S0b");
+    }
+
+    #[test]
+    fn test_target_xml() {
+        let mut state = InteractiveState::new();
+        let mut stub = Stub::new(&mut state);
+
+        let first = reply(&mut stub, "qXfer:features:read:target.xml:0,a");
+        assert_eq!(first, "m<?xml vers");
+        let rest = reply(&mut stub, "qXfer:features:read:target.xml:a,fff");
+        assert!(rest.starts_with('l') && rest.ends_with("</target>\n"));
+        assert_eq!(
+            reply(&mut stub, "qXfer:features:read:other.xml:0,10"),
+            "E00"
+        );
+        assert_eq!(reply(&mut stub, "vMustReplyEmpty"), "");
+    }
+}
@@ -0,0 +1,177 @@
+use std::fmt::Write;
+
+use arbitrary_int::u6;
+use clap::ValueEnum;
+
+/// How 6-bit words are laid out in a binary file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum ImageFormat {
+    /// One word per byte, the two high bits are zero
+    #[default]
+    Padded,
+    /// Four words per three bytes, little-endian
+    Packed,
+    /// Intel HEX records of padded words for EEPROM programmers
+    IntelHex,
+    /// A Verilog `$readmemb` text image
+    Readmemb,
+    /// One `address: word` line per word
+    Listing,
+}
+
+#[derive(Debug)]
+pub enum ImageError {
+    /// A padded byte with either of the two high bits set.
+    InvalidWord { index: usize },
+    /// Text images are only written for other tools, they can't be loaded.
+    WriteOnly(ImageFormat),
+}
+
+/// Lay out `binary` in `format`; text formats record `offset` as the load address.
+pub fn encode(binary: &[u6], offset: usize, format: ImageFormat) -> Vec<u8> {
+    match format {
+        ImageFormat::Padded => binary.iter().map(|word| word.value()).collect(),
+        ImageFormat::Packed => {
+            let mut bytes = Vec::with_capacity((binary.len() * 6).div_ceil(8));
+            for chunk in binary.chunks(4) {
+                let group = chunk.iter().enumerate().fold(0u32, |group, (index, word)| {
+                    group | (u32::from(word.value()) << (index * 6))
+                });
+                // A trailing group only needs enough bytes to hold its words.
+                let length = (chunk.len() * 6).div_ceil(8);
+                bytes.extend_from_slice(&group.to_le_bytes()[..length]);
+            }
+            bytes
+        }
+        ImageFormat::IntelHex => intel_hex(binary, offset).into_bytes(),
+        ImageFormat::Readmemb => {
+            let mut text = format!("@{offset:03X}\n");
+            for word in binary {
+                writeln!(text, "{word:0>6b}").unwrap();
+            }
+            text.into_bytes()
+        }
+        ImageFormat::Listing => {
+            let mut text = String::new();
+            for (index, word) in binary.iter().enumerate() {
+                writeln!(text, "{:03X}: {word:0>6b}", offset + index).unwrap();
+            }
+            text.into_bytes()
+        }
+    }
+}
+
+fn intel_hex(binary: &[u6], offset: usize) -> String {
+    const DATA: u8 = 0x00;
+    const END_OF_FILE: u8 = 0x01;
+
+    fn record(text: &mut String, address: u16, kind: u8, data: &[u8]) {
+        let [high, low] = address.to_be_bytes();
+        let sum = [data.len() as u8, high, low, kind]
+            .iter()
+            .chain(data)
+            .fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+
+        write!(text, ":{:02X}{address:04X}{kind:02X}", data.len()).unwrap();
+        data.iter()
+            .for_each(|byte| write!(text, "{byte:02X}").unwrap());
+        writeln!(text, "{:02X}", sum.wrapping_neg()).unwrap();
+    }
+
+    let mut text = String::new();
+    for (index, chunk) in binary.chunks(16).enumerate() {
+        let data: Vec<u8> = chunk.iter().map(|word| word.value()).collect();
+        record(&mut text, (offset + index * 16) as u16, DATA, &data);
+    }
+    record(&mut text, 0, END_OF_FILE, &[]);
+    text
+}
+
+/// Read a binary back into words; a packed image may end with a zero word of padding.
+pub fn decode(bytes: &[u8], format: ImageFormat) -> Result<Vec<u6>, ImageError> {
+    match format {
+        ImageFormat::Padded => bytes
+            .iter()
+            .enumerate()
+            .map(|(index, byte)| u6::try_new(*byte).map_err(|_| ImageError::InvalidWord { index }))
+            .collect(),
+        ImageFormat::Packed => {
+            let mut binary = Vec::with_capacity(bytes.len() * 8 / 6);
+            for chunk in bytes.chunks(3) {
+                let mut group = [0; 4];
+                group[..chunk.len()].copy_from_slice(chunk);
+                let group = u32::from_le_bytes(group);
+                let length = chunk.len() * 8 / 6;
+                binary.extend(
+                    (0..length).map(|index| u6::new((group >> (index * 6)) as u8 & 0b111111)),
+                );
+            }
+            Ok(binary)
+        }
+        format => Err(ImageError::WriteOnly(format)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arbitrary_int::u6;
+
+    use super::{decode, encode, ImageFormat};
+
+    #[test]
+    fn test_packed_layout() {
+        let binary = [0b000001, 0b000010, 0b000011, 0b111111].map(u6::new);
+        assert_eq!(
+            encode(&binary, 0, ImageFormat::Packed),
+            vec![0b1000_0001, 0b0011_0000, 0b1111_1100]
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let binary: Vec<u6> = (0..64).map(u6::new).collect();
+        for format in [ImageFormat::Padded, ImageFormat::Packed] {
+            for length in [0, 1, 2, 3, 4, 5, 63, 64] {
+                let bytes = encode(&binary[..length], 0, format);
+                let decoded = decode(&bytes, format).unwrap();
+                assert_eq!(&decoded[..length], &binary[..length], "{format:?} {length}");
+                assert!(decoded[length..].iter().all(|word| word.value() == 0));
+            }
+        }
+    }
+
+    #[test]
+    fn test_packed_is_smaller() {
+        let binary = vec![u6::new(0b101010); 400];
+        assert_eq!(encode(&binary, 0, ImageFormat::Packed).len(), 300);
+    }
+
+    #[test]
+    fn test_intel_hex() {
+        let binary: Vec<u6> = (0..18).map(u6::new).collect();
+        let text = String::from_utf8(encode(&binary, 0x100, ImageFormat::IntelHex)).unwrap();
+        assert_eq!(
+            text.lines().collect::<Vec<_>>(),
+            vec![
+                ":10010000000102030405060708090A0B0C0D0E0F77",
+                ":020110001011CC",
+                ":00000001FF",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_text_images() {
+        let binary = [0b000001, 0b111111].map(u6::new);
+        let readmemb = encode(&binary, 0x40, ImageFormat::Readmemb);
+        assert_eq!(readmemb, b"@040\n000001\n111111\n");
+        let listing = encode(&binary, 0x40, ImageFormat::Listing);
+        assert_eq!(listing, b"040: 000001\n041: 111111\n");
+        assert!(decode(&listing, ImageFormat::Listing).is_err());
+    }
+
+    #[test]
+    fn test_invalid_padded_byte() {
+        assert!(decode(&[0b000001, 0b1000000], ImageFormat::Padded).is_err());
+    }
+}
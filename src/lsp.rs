@@ -0,0 +1,524 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    io::{self, stdin, stdout, BufRead, Write},
+    ops::Range,
+};
+
+use arbitrary_int::u12;
+use serde_json::{json, Value};
+use strum::IntoEnumIterator;
+
+use crate::{
+    compilation::{
+        compile_to_binary,
+        lint::{label_uses, lint, LabelUse, LintLevels},
+        tokens::{Keyword, Token, TokenKind},
+        CompileInfo, DiagLevel,
+    },
+    disassembler::disassemble,
+    instruction::{Instruction, Operation, Register},
+    message::{read_message, write_message},
+};
+
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// LSP `DiagnosticSeverity`, `CompletionItemKind` and `TextDocumentSyncKind` values.
+const SEVERITY_ERROR: u64 = 1;
+const SEVERITY_WARNING: u64 = 2;
+const COMPLETION_KEYWORD: u64 = 14;
+const COMPLETION_CONSTANT: u64 = 21;
+const SYNC_FULL: u64 = 1;
+
+/// A document compiled to answer one request.
+struct Analysis<'a> {
+    text: &'a str,
+    compile_info: CompileInfo,
+    labels: Vec<LabelUse>,
+}
+
+impl<'a> Analysis<'a> {
+    fn new(text: &'a str) -> Self {
+        // ASCII uppercasing keeps every byte offset valid in the original text.
+        let upper = text.to_ascii_uppercase();
+        let mut compile_info = compile_to_binary(&upper, u12::new(0));
+        let warnings = lint(&upper, &compile_info.tokens, &LintLevels::default());
+        compile_info.diagnostics.extend(warnings);
+        let labels = label_uses(&compile_info.tokens, &upper);
+        Self {
+            text,
+            compile_info,
+            labels,
+        }
+    }
+
+    fn token_at(&self, offset: usize) -> Option<&Token> {
+        self.compile_info.tokens.iter().find(|token| {
+            (token.span.start..=token.span.end).contains(&offset)
+                && matches!(token.kind, TokenKind::Identifier | TokenKind::Keyword(_))
+        })
+    }
+
+    fn label_at(&self, offset: usize) -> Option<&LabelUse> {
+        self.labels
+            .iter()
+            .find(|label| (label.span.start..=label.span.end).contains(&offset))
+    }
+
+    fn diagnostics(&self) -> Vec<Value> {
+        self.compile_info
+            .diagnostics
+            .iter()
+            .map(|diagnostic| {
+                json!({
+                    "range": range(self.text, diagnostic.span.start..diagnostic.span.end),
+                    "severity": match diagnostic.level {
+                        DiagLevel::Fatal => SEVERITY_ERROR,
+                        DiagLevel::Warning => SEVERITY_WARNING,
+                    },
+                    "source": "dianac",
+                    "message": format!("{}: {}", diagnostic.kind, diagnostic.kind.help()),
+                })
+            })
+            .collect()
+    }
+
+    /// Where the label under `offset` is defined, if it is one the compiler knows.
+    fn definition(&self, offset: usize) -> Option<&LabelUse> {
+        let name = &self.label_at(offset)?.name;
+        self.compile_info.symbol_table.get(name.as_str())?;
+        self.labels
+            .iter()
+            .find(|label| label.definition && label.name == *name)
+    }
+
+    fn references(&self, offset: usize, include_declaration: bool) -> Vec<&LabelUse> {
+        let Some(target) = self.label_at(offset) else {
+            return Vec::new();
+        };
+        self.labels
+            .iter()
+            .filter(|label| label.name == target.name && (include_declaration || !label.definition))
+            .collect()
+    }
+
+    /// A keyword's expansion on this line, or a label's address.
+    fn hover(&self, offset: usize) -> Option<(String, Range<usize>)> {
+        let token = self.token_at(offset)?;
+        let range = token.span.start..token.span.end;
+
+        if let Some(label) = self.label_at(offset) {
+            let address = self.compile_info.symbol_table.get(label.name.as_str())?;
+            return Some((
+                format!("`{}` = `{:#05X}`", label.name, address.value()),
+                range,
+            ));
+        }
+
+        let keyword = &self.text[range.clone()].to_ascii_uppercase();
+        let line = self.text[..range.start].matches('\n').count() + 1;
+        let Some(statement) = self
+            .compile_info
+            .debug_info
+            .statements
+            .iter()
+            .find(|statement| statement.line == line)
+        else {
+            return Some((format!("**{keyword}** produces no code"), range));
+        };
+
+        let start = statement.address.value() as usize;
+        let end = start + statement.length.value() as usize;
+        // Statements that fail to assemble leave the binary short.
+        let Some(words) = self.compile_info.binary.get(start..end) else {
+            return Some((format!("**{keyword}**"), range));
+        };
+        let length = words.len();
+        let plural = if length == 1 { "word" } else { "words" };
+        let modified = if statement.data {
+            "is data".to_string()
+        } else {
+            match modified_registers(words) {
+                registers if registers.is_empty() => "modifies no registers".to_string(),
+                registers => {
+                    let registers: Vec<_> =
+                        registers.iter().map(|name| format!("`{name}`")).collect();
+                    format!("modifies {}", registers.join(", "))
+                }
+            }
+        };
+        Some((
+            format!("**{keyword}** expands to {length} {plural} and {modified}"),
+            range,
+        ))
+    }
+
+    fn completion(&self) -> Vec<Value> {
+        let keywords = Keyword::iter().map(|keyword| {
+            let name: &'static str = keyword.into();
+            json!({ "label": name, "kind": COMPLETION_KEYWORD })
+        });
+        let labels: BTreeSet<_> = self
+            .compile_info
+            .symbol_table
+            .iter()
+            // Generated and anonymous labels can't be written in the source.
+            .filter(|(label, _)| !label.starts_with(['#', '+', '-']))
+            .map(|(label, address)| (label.to_string(), address.value()))
+            .collect();
+        let labels = labels.into_iter().map(|(label, address)| {
+            json!({ "label": label, "kind": COMPLETION_CONSTANT, "detail": format!("{address:#05X}") })
+        });
+        keywords.chain(labels).collect()
+    }
+}
+
+/// Registers written by the instructions in `words`: the first operand of `NOR` and `C` for a load.
+fn modified_registers(words: &[arbitrary_int::u6]) -> BTreeSet<char> {
+    disassemble(words, 0)
+        .iter()
+        .filter(|line| !(0b001100..=0b001111).contains(&line.words[0].value()))
+        .filter_map(|line| {
+            let instruction = Instruction::new_with_raw_value(line.words[0]);
+            match (instruction.operation(), instruction.one()) {
+                (Operation::Nor, Register::A) => Some('A'),
+                (Operation::Nor, Register::B) => Some('B'),
+                (Operation::Nor, Register::C) | (Operation::Load, _) => Some('C'),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// An LSP position, counting characters in UTF-16 code units.
+fn position(text: &str, offset: usize) -> Value {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+    json!({
+        "line": before.matches('\n').count(),
+        "character": before[line_start..].encode_utf16().count(),
+    })
+}
+
+fn range(text: &str, range: Range<usize>) -> Value {
+    json!({ "start": position(text, range.start), "end": position(text, range.end) })
+}
+
+/// The byte offset of an LSP position, clamped to the end of its line.
+fn offset(text: &str, position: &Value) -> Option<usize> {
+    let line = position["line"].as_u64()? as usize;
+    let character = position["character"].as_u64()? as usize;
+
+    let line_start = match line {
+        0 => 0,
+        line => text.match_indices('\n').nth(line - 1)?.0 + 1,
+    };
+    let mut units = 0;
+    for (index, char) in text[line_start..].char_indices() {
+        if units >= character || char == '\n' {
+            return Some(line_start + index);
+        }
+        units += char.len_utf16();
+    }
+    Some(text.len())
+}
+
+struct Server<W: Write> {
+    writer: W,
+    documents: HashMap<String, String>,
+}
+
+/// Speak the Language Server Protocol over stdin and stdout until the editor exits.
+pub fn serve() -> io::Result<()> {
+    Server::new(stdout().lock()).run(&mut stdin().lock())
+}
+
+impl<W: Write> Server<W> {
+    fn new(writer: W) -> Self {
+        Self {
+            writer,
+            documents: HashMap::new(),
+        }
+    }
+
+    fn run(&mut self, reader: &mut impl BufRead) -> io::Result<()> {
+        while let Some(message) = read_message(reader)? {
+            if !self.handle(&message)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Answer a request or apply a notification, returning false on `exit`.
+    fn handle(&mut self, message: &Value) -> io::Result<bool> {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+
+        let result = match method {
+            "initialize" => json!({
+                "capabilities": {
+                    "textDocumentSync": SYNC_FULL,
+                    "definitionProvider": true,
+                    "referencesProvider": true,
+                    "hoverProvider": true,
+                    "completionProvider": {},
+                },
+                "serverInfo": { "name": "dianac", "version": env!("CARGO_PKG_VERSION") },
+            }),
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents.insert(uri.to_string(), text.to_string());
+                return self.publish_diagnostics(uri).map(|_| true);
+            }
+            "textDocument/didChange" => {
+                // Full sync, so the last change holds the whole document.
+                if let Some(text) = params["contentChanges"]
+                    .as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str())
+                {
+                    self.documents.insert(uri.to_string(), text.to_string());
+                }
+                return self.publish_diagnostics(uri).map(|_| true);
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                return self.publish_diagnostics(uri).map(|_| true);
+            }
+            "textDocument/definition" => {
+                self.with_analysis(uri, params, |analysis, text, offset| {
+                    analysis.definition(offset).map_or(
+                        Value::Null,
+                        |label| json!({ "uri": uri, "range": range(text, label.span.as_range()) }),
+                    )
+                })
+            }
+            "textDocument/references" => {
+                self.with_analysis(uri, params, |analysis, text, offset| {
+                    let include_declaration = params["context"]["includeDeclaration"]
+                        .as_bool()
+                        .unwrap_or(true);
+                    let locations: Vec<_> = analysis
+                    .references(offset, include_declaration)
+                    .into_iter()
+                    .map(|label| json!({ "uri": uri, "range": range(text, label.span.as_range()) }))
+                    .collect();
+                    json!(locations)
+                })
+            }
+            "textDocument/hover" => self.with_analysis(uri, params, |analysis, text, offset| {
+                analysis
+                    .hover(offset)
+                    .map_or(Value::Null, |(value, hovered)| {
+                        json!({
+                            "contents": { "kind": "markdown", "value": value },
+                            "range": range(text, hovered),
+                        })
+                    })
+            }),
+            "textDocument/completion" => {
+                self.with_analysis(uri, params, |analysis, _, _| json!(analysis.completion()))
+            }
+            "shutdown" => Value::Null,
+            "exit" => return Ok(false),
+            _ if message.get("id").is_some() => {
+                return self
+                    .send(json!({
+                        "jsonrpc": "2.0",
+                        "id": message["id"],
+                        "error": { "code": METHOD_NOT_FOUND, "message": format!("Unsupported method `{method}`") },
+                    }))
+                    .map(|_| true);
+            }
+            // Other notifications need no answer.
+            _ => return Ok(true),
+        };
+
+        self.send(json!({ "jsonrpc": "2.0", "id": message["id"], "result": result }))?;
+        Ok(true)
+    }
+
+    fn with_analysis(
+        &self,
+        uri: &str,
+        params: &Value,
+        answer: impl FnOnce(&Analysis, &str, usize) -> Value,
+    ) -> Value {
+        let Some(text) = self.documents.get(uri) else {
+            return Value::Null;
+        };
+        let Some(offset) = offset(text, &params["position"]) else {
+            return Value::Null;
+        };
+        answer(&Analysis::new(text), text, offset)
+    }
+
+    fn publish_diagnostics(&mut self, uri: &str) -> io::Result<()> {
+        let diagnostics = self
+            .documents
+            .get(uri)
+            .map(|text| Analysis::new(text).diagnostics())
+            .unwrap_or_default();
+        self.send(json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": { "uri": uri, "diagnostics": diagnostics },
+        }))
+    }
+
+    fn send(&mut self, message: Value) -> io::Result<()> {
+        write_message(&mut self.writer, &message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use serde_json::{json, Value};
+
+    use super::{offset, position, Analysis, Server};
+    use crate::message::read_message;
+
+    const URI: &str = "file:///test.dcl";
+
+    fn request(server: &mut Server<Vec<u8>>, method: &str, params: Value) -> Vec<Value> {
+        server.writer.clear();
+        server
+            .handle(&json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params }))
+            .unwrap();
+
+        let mut reader = Cursor::new(&server.writer);
+        std::iter::from_fn(|| read_message(&mut reader).unwrap()).collect()
+    }
+
+    fn open(source: &str) -> (Server<Vec<u8>>, Vec<Value>) {
+        let mut server = Server::new(Vec::new());
+        let messages = request(
+            &mut server,
+            "textDocument/didOpen",
+            json!({ "textDocument": { "uri": URI, "text": source } }),
+        );
+        (server, messages)
+    }
+
+    fn at(line: u64, character: u64) -> Value {
+        json!({ "textDocument": { "uri": URI }, "position": { "line": line, "character": character } })
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let (mut server, messages) = open("nor a 1\nmov q 1");
+        let diagnostics = &messages[0]["params"]["diagnostics"];
+        assert_eq!(diagnostics.as_array().unwrap().len(), 2);
+        assert_eq!(diagnostics[0]["severity"], 1);
+        assert_eq!(
+            diagnostics[0]["range"]["start"],
+            json!({ "line": 1, "character": 4 })
+        );
+
+        let messages = request(
+            &mut server,
+            "textDocument/didChange",
+            json!({ "textDocument": { "uri": URI }, "contentChanges": [{ "text": "HLT" }] }),
+        );
+        assert_eq!(messages[0]["params"]["diagnostics"], json!([]));
+    }
+
+    #[test]
+    fn test_definition_and_references() {
+        let source = "LAB MAIN\nLAB .LOOP\nPC .LOOP\nLAB OTHER\nLAB .LOOP\nPC MAIN.LOOP\nPC .LOOP";
+        let (mut server, _) = open(source);
+
+        let messages = request(&mut server, "textDocument/definition", at(5, 9));
+        assert_eq!(
+            messages[0]["result"]["range"]["start"],
+            json!({ "line": 1, "character": 4 })
+        );
+
+        let messages = request(&mut server, "textDocument/definition", at(6, 4));
+        assert_eq!(
+            messages[0]["result"]["range"]["start"],
+            json!({ "line": 4, "character": 4 })
+        );
+
+        let mut params = at(2, 3);
+        params["context"] = json!({ "includeDeclaration": false });
+        let messages = request(&mut server, "textDocument/references", params);
+        let lines: Vec<_> = messages[0]["result"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|location| location["range"]["start"]["line"].as_u64().unwrap())
+            .collect();
+        assert_eq!(lines, vec![2, 5]);
+
+        let messages = request(&mut server, "textDocument/definition", at(2, 0));
+        assert_eq!(messages[0]["result"], Value::Null);
+    }
+
+    #[test]
+    fn test_hover() {
+        let analysis = Analysis::new("nor a b\nLAB LOOP\nlod loop\nSET 5\nMOV A B");
+        let hover = |offset| analysis.hover(offset).map(|(text, _)| text);
+
+        assert_eq!(
+            hover(1).unwrap(),
+            "**NOR** expands to 1 word and modifies `A`"
+        );
+        assert_eq!(hover(9).unwrap(), "**LAB** produces no code");
+        assert_eq!(hover(14).unwrap(), "`LOOP` = `0x001`");
+        assert_eq!(
+            hover(18).unwrap(),
+            "**LOD** expands to 3 words and modifies `C`"
+        );
+        assert_eq!(hover(27).unwrap(), "**SET** expands to 1 word and is data");
+        assert!(hover(33).unwrap().contains("modifies `A`"));
+        assert_eq!(hover(5), None);
+    }
+
+    #[test]
+    fn test_hover_with_errors() {
+        let (mut server, _) = open("PC NOWHERE\nNOP\nNOR A B\n");
+        let messages = request(&mut server, "textDocument/hover", at(2, 1));
+        assert_eq!(messages[0]["result"]["contents"]["value"], "**NOR**");
+
+        let messages = request(&mut server, "textDocument/hover", at(7, 0));
+        assert_eq!(messages[0]["result"], Value::Null);
+    }
+
+    #[test]
+    fn test_completion() {
+        let (mut server, _) = open("LAB START\nLAB .INNER\nIF [A == 1]\nEND\nLAB +\nHLT");
+        let messages = request(&mut server, "textDocument/completion", at(5, 0));
+        let labels: Vec<_> = messages[0]["result"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["label"].as_str().unwrap().to_string())
+            .collect();
+        assert!(labels.contains(&"ADD12".to_string()));
+        assert!(labels.contains(&"HLT".to_string()));
+        assert!(labels.ends_with(&["START".to_string(), "START.INNER".to_string()]));
+    }
+
+    #[test]
+    fn test_positions() {
+        let text = "ab\nçd\n";
+        assert_eq!(position(text, 6), json!({ "line": 1, "character": 2 }));
+        assert_eq!(offset(text, &json!({ "line": 1, "character": 1 })), Some(5));
+        assert_eq!(offset(text, &json!({ "line": 0, "character": 9 })), Some(2));
+        assert_eq!(offset(text, &json!({ "line": 4, "character": 0 })), None);
+    }
+
+    #[test]
+    fn test_unknown_request() {
+        let (mut server, _) = open("HLT");
+        let messages = request(&mut server, "workspace/symbol", json!({}));
+        assert_eq!(messages[0]["error"]["code"], -32601);
+        assert!(!server
+            .handle(&json!({ "jsonrpc": "2.0", "method": "exit" }))
+            .unwrap());
+    }
+}
@@ -1,13 +1,16 @@
 use arbitrary_int::{u12, u6};
-use clap::{Parser as ArgParser, Subcommand};
+use clap::{Parser as ArgParser, Subcommand, ValueEnum};
 use colored::Colorize;
 use std::{
-    fs,
-    io::{stdin, stdout, BufRead, Write},
+    collections::{BTreeSet, HashMap},
+    fs::{self, File},
+    io::{stdin, stdout, BufRead, BufWriter, Write},
+    net::{Ipv4Addr, TcpListener},
     path::{Path, PathBuf},
     str::FromStr,
+    sync::Arc,
     thread::sleep,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 #[cfg(test)]
@@ -17,16 +20,36 @@ mod compilation;
 mod emulation;
 
 mod character_encoding;
+mod dap;
+mod disassembler;
 mod errors;
+mod formatter;
+mod gdb;
+mod image;
 mod instruction;
+mod lsp;
+mod message;
+mod testing;
 mod utils;
 
 use errors::Error;
 
 use crate::{
-    compilation::{compile_to_binary, DiagLevel},
-    emulation::InteractiveState,
-    utils::tuple_as_usize,
+    character_encoding::{decode_character, encode_character},
+    compilation::{
+        compile_to_binary, compile_to_object,
+        debug_info::DebugInfo,
+        diagnostic::DiagKind,
+        lexer::Cursor,
+        lint::{lint, Lint, LintLevels},
+        object::{link, Object},
+        CompileInfo, DiagLevel, Diagnostic,
+    },
+    emulation::{
+        ExecutionError, History, InteractiveState, Profile, RomPolicy, Trace, TrapHandler,
+    },
+    image::ImageFormat,
+    utils::{tuple_as_usize, usize_as_tuple},
 };
 
 /// An emulator, compiler, and interpreter for the Diana Compiled Language
@@ -41,38 +64,384 @@ struct Cli {
 enum Command {
     /// Start the interactive emulation REPL
     Repl,
-    /// Compile a static binary (6-bit bytes are padded with zeros)
+    /// Compile a static binary
     Compile {
         /// An input file handle (can be /dev/stdin)
         source: PathBuf,
         /// File path to compiled binary
         destination: Option<PathBuf>,
         /// Memory offset for the compiled code (in bytes)
+        #[arg(default_value_t = 0, conflicts_with = "object")]
         offset: usize,
         /// Suppress all non-fatal diagnostics
         #[arg(short, long)]
         quiet: bool,
+        /// Emit a relocatable object for `link` instead (it is placed by `link --offset`)
+        #[arg(long)]
+        object: bool,
+        /// Don't report a lint
+        #[arg(short = 'A', long, value_enum)]
+        allow: Vec<Lint>,
+        /// Report a lint as an error
+        #[arg(short = 'D', long, value_enum)]
+        deny: Vec<Lint>,
+        /// Compile again whenever the source changes
+        #[arg(short, long)]
+        watch: bool,
+        /// Layout of the compiled binary
+        #[arg(short, long, value_enum, default_value_t, conflicts_with = "object")]
+        format: ImageFormat,
+    },
+    /// Link relocatable objects into a static binary
+    Link {
+        /// Object files, placed in the given order
+        #[arg(required = true)]
+        objects: Vec<PathBuf>,
+        /// File path to linked binary
+        #[arg(short = 'o', long)]
+        destination: Option<PathBuf>,
+        /// Memory offset for the linked code (in bytes)
+        #[arg(long, default_value_t = 0)]
+        offset: usize,
+        /// Layout of the linked binary
+        #[arg(short, long, value_enum, default_value_t)]
+        format: ImageFormat,
+    },
+    /// Run a program until it halts and print the final state
+    Run {
+        /// A DCL source file (`.dcl`) or a compiled binary
+        program: PathBuf,
+        /// Memory offset to load and start the program at (in bytes)
+        #[arg(default_value_t = 0)]
+        offset: usize,
+        /// Layout of a compiled binary
+        #[arg(short, long, value_enum, default_value_t)]
+        format: ImageFormat,
+        /// Treat `TRP` and `IRT` as illegal opcodes
+        #[arg(long)]
+        strict: bool,
+        /// What a write to ROM (`0xF3E..=0xFFF`) does
+        #[arg(long, value_enum, default_value_t)]
+        rom: RomArg,
+        /// Write every executed instruction to a file
+        #[arg(long)]
+        trace: Option<PathBuf>,
+        /// Print where the cycles were spent, by label and by source line
+        #[arg(long)]
+        profile: bool,
+        /// Write the profile as folded stacks for flamegraph tools
+        #[arg(long)]
+        profile_folded: Option<PathBuf>,
+        /// Write an lcov report of the executed lines and `LIH` branches
+        #[arg(long)]
+        coverage: Option<PathBuf>,
+        /// Run again whenever the program changes
+        #[arg(short, long)]
+        watch: bool,
+    },
+    /// Print a compiled binary as DCL statements
+    Disassemble {
+        /// A compiled binary
+        binary: PathBuf,
+        /// Memory offset the binary is loaded at (in bytes)
+        #[arg(default_value_t = 0)]
+        offset: usize,
+        /// Layout of the compiled binary
+        #[arg(short, long, value_enum, default_value_t)]
+        format: ImageFormat,
+    },
+    /// Run the DCL programs in a directory and check their `# RESULT` comments
+    Test {
+        /// A directory searched for `.dcl` files, or a single file
+        path: PathBuf,
+        /// Stop a program that is still running after this many cycles
+        #[arg(long, default_value_t = testing::CYCLE_LIMIT)]
+        cycles: u64,
+    },
+    /// Format DCL source files in place
+    Fmt {
+        /// DCL source files (`.dcl`)
+        #[arg(required = true)]
+        sources: Vec<PathBuf>,
+        /// Only list the files that are not formatted and fail if there are any
+        #[arg(long)]
+        check: bool,
+    },
+    /// Debug DCL files from an editor with the Debug Adapter Protocol over stdio
+    Dap,
+    /// Check and navigate DCL files from an editor with the Language Server Protocol over stdio
+    Lsp,
+    /// Debug a program with GDB over a local TCP port (`target remote :1234`)
+    Gdb {
+        /// A DCL source file (`.dcl`) or a compiled binary
+        program: PathBuf,
+        /// Memory offset to load and start the program at (in bytes)
+        #[arg(default_value_t = 0)]
+        offset: usize,
+        /// Layout of a compiled binary
+        #[arg(short, long, value_enum, default_value_t)]
+        format: ImageFormat,
+        /// Port to wait for GDB on
+        #[arg(short, long, default_value_t = 1234)]
+        port: u16,
     },
 }
 
+/// The command line names of each `RomPolicy`.
+#[derive(Clone, Copy, Default, ValueEnum)]
+enum RomArg {
+    /// Discard the write, like hardware without a write enable
+    Ignore,
+    /// Stop with an error
+    #[default]
+    Fault,
+    /// Store the value; later reads return it instead of the ROM
+    AllowAsRam,
+}
+
+impl From<RomArg> for RomPolicy {
+    fn from(value: RomArg) -> Self {
+        match value {
+            RomArg::Ignore => Self::Ignore,
+            RomArg::Fault => Self::Fault,
+            RomArg::AllowAsRam => Self::AllowAsRam,
+        }
+    }
+}
+
+impl From<RomPolicy> for RomArg {
+    fn from(value: RomPolicy) -> Self {
+        match value {
+            RomPolicy::Ignore => Self::Ignore,
+            RomPolicy::Fault => Self::Fault,
+            RomPolicy::AllowAsRam => Self::AllowAsRam,
+        }
+    }
+}
+
 fn main() -> Result<(), Error> {
     let args = Cli::parse();
 
     match args.command {
         Command::Repl => emulation_repl()?,
+        Command::Compile {
+            source,
+            destination,
+            offset: _,
+            quiet,
+            object: true,
+            format: _,
+            allow,
+            deny,
+            watch,
+        } => {
+            // Every label of an object is exported, so it may be used by another object.
+            let lints = LintLevels::new(&[&[Lint::UnusedLabel], &allow[..]].concat(), &deny);
+            let destination = destination.unwrap_or_else(|| source.with_extension("o"));
+            let mut failed = false;
+            repeat_on_change(&source, watch, || {
+                match display_object_compilation(&source, quiet, &lints)? {
+                    Some(object) => fs::write(&destination, object.to_string())?,
+                    None => failed = true,
+                }
+                Ok(())
+            })?;
+            if failed {
+                std::process::exit(1);
+            }
+        }
         Command::Compile {
             source,
             destination,
             offset,
             quiet,
+            object: false,
+            format,
+            allow,
+            deny,
+            watch,
         } => {
-            if let Some(instructions) = display_compilation(&source, offset, quiet)? {
-                fs::write(
-                    destination.unwrap_or_else(|| source.with_extension("")),
-                    instructions.iter().map(|i| i.value()).collect::<Vec<u8>>(),
-                )?;
+            let lints = LintLevels::new(&allow, &deny);
+            let destination = destination.unwrap_or_else(|| source.with_extension(""));
+            let mut failed = false;
+            repeat_on_change(&source, watch, || {
+                match display_compilation(&source, offset, quiet, &lints)? {
+                    Some(compile_info) => fs::write(
+                        &destination,
+                        image::encode(&compile_info.binary, offset, format),
+                    )?,
+                    None => failed = true,
+                }
+                Ok(())
+            })?;
+            if failed {
+                std::process::exit(1);
             }
         }
+        Command::Link {
+            objects,
+            destination,
+            offset,
+            format,
+        } => {
+            let objects = objects
+                .iter()
+                .map(|path| Ok(Object::from_str(&fs::read_to_string(path)?)?))
+                .collect::<Result<Vec<_>, Error>>()?;
+            let binary = link(&objects, u12::new(offset as u16))?;
+            fs::write(
+                destination.unwrap_or_else(|| PathBuf::from("a.out")),
+                image::encode(&binary, offset, format),
+            )?;
+        }
+        Command::Run {
+            program,
+            offset,
+            format,
+            strict,
+            rom,
+            trace,
+            profile,
+            profile_folded,
+            coverage,
+            watch,
+        } => {
+            let mut failed = false;
+            repeat_on_change(&program, watch, || {
+                let Some(Program {
+                    binary,
+                    compile_info,
+                }) = load_program(&program, offset, format)?
+                else {
+                    failed = true;
+                    return Ok(());
+                };
+                let mut state = new_state();
+                state.strict = strict;
+                state.memory.rom_policy = rom.into();
+                if let Some(path) = &trace {
+                    state.trace =
+                        Some(Trace::new(0).with_writer(BufWriter::new(File::create(path)?)));
+                }
+                if profile || profile_folded.is_some() || coverage.is_some() {
+                    state.profile = Some(Profile::new());
+                }
+                state.memory.store_array(offset, &binary)?;
+                state.program_counter.set(usize_as_tuple(offset));
+                if let Some(compile_info) = &compile_info {
+                    state.checks = compile_info.debug_info.checks.clone();
+                }
+                let result = state.consume_until_halt();
+                if let Err(error) = result {
+                    report_fault(&state, error);
+                    if let Some((check, left, right)) = state.failed_check() {
+                        let diagnostic = Diagnostic {
+                            level: DiagLevel::Fatal,
+                            span: check.span,
+                            kind: DiagKind::FailedCheck { left, right },
+                        };
+                        diagnostic.emit(&fs::read_to_string(&program)?, &program);
+                    }
+                }
+                print_state(&state);
+
+                if let Some(recorded) = &state.profile {
+                    let (symbol_table, debug_info) = compile_info
+                        .map(|info| (info.symbol_table, info.debug_info))
+                        .unwrap_or_default();
+                    if profile {
+                        print_profile(recorded, &symbol_table, &debug_info);
+                    }
+                    if let Some(path) = &profile_folded {
+                        fs::write(path, recorded.folded(&symbol_table, &debug_info))?;
+                    }
+                    if let Some(path) = &coverage {
+                        let source = fs::canonicalize(&program)?;
+                        let halted_at = result
+                            .is_ok()
+                            .then(|| tuple_as_usize(state.program_counter.as_tuple()));
+                        fs::write(
+                            path,
+                            emulation::lcov(recorded, halted_at, &source, &debug_info),
+                        )?;
+                    }
+                }
+                Ok(result?)
+            })?;
+            if failed {
+                std::process::exit(1);
+            }
+        }
+        Command::Disassemble {
+            binary,
+            offset,
+            format,
+        } => {
+            let binary = image::decode(&fs::read(binary)?, format)?;
+            for line in disassembler::disassemble(&binary, offset) {
+                let words: Vec<_> = line
+                    .words
+                    .iter()
+                    .map(|word| format!("{word:0>6b}"))
+                    .collect();
+                println!(
+                    "{:0>3X}: {:<20} {}",
+                    line.address,
+                    words.join(" "),
+                    line.text
+                );
+            }
+        }
+        Command::Test { path, cycles } => {
+            if !run_tests(&path, cycles)? {
+                std::process::exit(1);
+            }
+        }
+        Command::Fmt { sources, check } => {
+            let mut unformatted = false;
+            for source in sources {
+                let code = fs::read_to_string(&source)?;
+                let formatted = formatter::format(&code);
+                if formatted == code {
+                    continue;
+                }
+                if check {
+                    println!("{} is not formatted", source.display());
+                    unformatted = true;
+                } else {
+                    fs::write(&source, formatted)?;
+                }
+            }
+            if unformatted {
+                std::process::exit(1);
+            }
+        }
+        Command::Dap => dap::serve()?,
+        Command::Lsp => lsp::serve()?,
+        Command::Gdb {
+            program,
+            offset,
+            format,
+            port,
+        } => {
+            let Some(Program {
+                binary,
+                compile_info,
+            }) = load_program(&program, offset, format)?
+            else {
+                std::process::exit(1);
+            };
+            let mut state = new_state();
+            state.memory.store_array(offset, &binary)?;
+            state.program_counter.set(usize_as_tuple(offset));
+            if let Some(compile_info) = compile_info {
+                state.checks = compile_info.debug_info.checks;
+            }
+
+            let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+            println!("Waiting for GDB on {}", listener.local_addr()?);
+            gdb::serve(&mut state, listener)?;
+        }
     }
 
     Ok(())
@@ -95,12 +464,30 @@ macro_rules! about {
     };
     (commands) => {
         concat!(
-            bold!("- run | r [speed]:"),
-            " run at speed (hz) until halt\n",
+            bold!("- run | r [clock]:"),
+            " run at a clock rate (cycles per second) until halt\n",
             bold!("- step | s:"),
             " step one instruction\n",
-            bold!("- interpret | i <dcl_source> [offset]:"),
-            " compile and store at the given offset\n",
+            bold!("- back | bk [count]:"),
+            " undo the last steps\n",
+            bold!("- reverse-continue | rc:"),
+            " undo steps until the previous breakpoint\n",
+            bold!("- break | bp [address]:"),
+            " toggle a breakpoint for run and reverse-continue, or list them\n",
+            bold!("- history [size]:"),
+            " set how many steps can be undone, or show usage\n",
+            bold!("- interrupt | int <address>:"),
+            " save the PC for IRT and jump to the address\n",
+            bold!("- strict [on|off]:"),
+            " treat TRP and IRT as illegal opcodes\n",
+            bold!("- trace | t on [file] | off | show [count]:"),
+            " record executed instructions (the last 1024 are kept)\n",
+            bold!("- rom [ignore|fault|allow-as-ram]:"),
+            " choose what a write to ROM does\n",
+            bold!("- interpret | i <program> [offset] [padded|packed]:"),
+            " compile a .dcl source or read a binary and store it at the given offset\n",
+            bold!("- load | l <binary> [offset] [padded|packed]:"),
+            " store a compiled binary at the given offset\n",
             bold!("- dump | d:"),
             " print the interactive state\n",
             bold!("- help | h:"),
@@ -111,10 +498,17 @@ macro_rules! about {
     };
 }
 
+/// How many executed instructions the REPL keeps for `trace show`.
+const TRACE_CAPACITY: usize = 1024;
+/// How many steps the REPL can undo by default.
+const HISTORY_CAPACITY: usize = 65536;
+
 pub fn emulation_repl() -> Result<(), Error> {
     // repl (read–eval–print loop)
     println!(about!(repl));
-    let mut state = InteractiveState::new();
+    let mut state = new_state();
+    state.history = Some(History::new(HISTORY_CAPACITY));
+    let mut breakpoints = BTreeSet::new();
 
     loop {
         print!("> ");
@@ -125,7 +519,7 @@ pub fn emulation_repl() -> Result<(), Error> {
 
         let args: Vec<&str> = line.split_whitespace().collect();
 
-        let command = match args.get(0) {
+        let command = match args.first() {
             Some(value) => value.to_lowercase(),
             None => continue,
         };
@@ -133,50 +527,164 @@ pub fn emulation_repl() -> Result<(), Error> {
         // add your changes to about!(commands) ╾━╤デ╦︻(▀̿Ĺ̯▀̿ ̿) or else!
         match command.as_str() {
             "run" | "r" => {
-                let sleep_time = if let Some(speed) = args.get(1) {
-                    1.0 / f64::from_str(*speed).unwrap()
-                } else {
-                    0.0
+                let clock = match args.get(1).map(|clock| f64::from_str(clock)) {
+                    Some(Ok(clock)) if clock > 0.0 => Some(clock),
+                    Some(_) => {
+                        println!("The clock rate must be a positive number of cycles per second");
+                        continue;
+                    }
+                    None => None,
                 };
 
-                while !state.is_halt() {
-                    sleep(Duration::from_secs_f64(sleep_time));
-                    state.consume_instruction();
+                // Pace against the start so rounding in each sleep doesn't accumulate.
+                let (start, start_cycles) = (Instant::now(), state.cycles);
+                let mut halted = state.is_halt();
+                while halted == Ok(false) {
+                    halted = state.consume_instruction().and_then(|()| state.is_halt());
+                    if let Some(clock) = clock {
+                        let target =
+                            Duration::from_secs_f64((state.cycles - start_cycles) as f64 / clock);
+                        sleep(target.saturating_sub(start.elapsed()));
+                    }
+                    if halted == Ok(false)
+                        && breakpoints.contains(&tuple_as_usize(state.program_counter.as_tuple()))
+                    {
+                        break;
+                    }
                 }
 
+                let program_counter = tuple_as_usize(state.program_counter.as_tuple());
+                match halted {
+                    Ok(true) => println!("Reached Halt {program_counter}"),
+                    Ok(false) => println!("Reached Breakpoint {program_counter}"),
+                    Err(error) => report_fault(&state, error),
+                }
+            }
+            "back" | "bk" => {
+                let count = args
+                    .get(1)
+                    .and_then(|count| count.parse().ok())
+                    .unwrap_or(1);
+                let undone = (0..count).take_while(|_| state.step_back()).count();
+                if undone < count {
+                    println!("Reached the start of the history after {undone} steps");
+                }
+            }
+            "reverse-continue" | "rc" => {
+                let mut undone = 0;
+                while state.step_back() {
+                    undone += 1;
+                    if breakpoints.contains(&tuple_as_usize(state.program_counter.as_tuple())) {
+                        break;
+                    }
+                }
                 println!(
-                    "Reached Halt {}",
+                    "Stepped back {undone} to {}",
                     tuple_as_usize(state.program_counter.as_tuple())
                 );
             }
+            "break" | "bp" => match args.get(1).map(|address| address.parse::<usize>()) {
+                Some(Ok(address)) => {
+                    if !breakpoints.remove(&address) {
+                        breakpoints.insert(address);
+                    }
+                }
+                Some(Err(error)) => println!("{error}"),
+                None => println!("Breakpoints: {breakpoints:?}"),
+            },
+            "history" => match args.get(1).map(|capacity| capacity.parse::<usize>()) {
+                Some(Ok(capacity)) => state.history = Some(History::new(capacity)),
+                Some(Err(error)) => println!("{error}"),
+                None => {
+                    let history = state.history.as_ref().unwrap();
+                    println!("{} of {} steps recorded", history.len(), history.capacity());
+                }
+            },
             "step" | "s" => {
-                state.consume_instruction();
+                if let Err(error) = state.consume_instruction() {
+                    report_fault(&state, error);
+                }
+            }
+            "interrupt" | "int" => {
+                let Some(Ok(address)) = args.get(1).map(|address| address.parse::<usize>()) else {
+                    println!("Missing interrupt vector address");
+                    continue;
+                };
+                state.interrupt(usize_as_tuple(address));
             }
+            "strict" => match args.get(1).copied() {
+                Some("on") => state.strict = true,
+                Some("off") => state.strict = false,
+                _ => println!("Strict mode is {}", if state.strict { "on" } else { "off" }),
+            },
+            "trace" | "t" => match args.get(1).copied() {
+                Some("on") => {
+                    let mut trace = Trace::new(TRACE_CAPACITY);
+                    if let Some(path) = args.get(2) {
+                        match File::create(path) {
+                            Ok(file) => trace = trace.with_writer(BufWriter::new(file)),
+                            Err(error) => {
+                                println!("Could not create `{path}`: {error}");
+                                continue;
+                            }
+                        }
+                    }
+                    state.trace = Some(trace);
+                }
+                Some("off") => state.trace = None,
+                Some("show") => {
+                    let count = args
+                        .get(2)
+                        .and_then(|count| count.parse().ok())
+                        .unwrap_or(10);
+                    match &state.trace {
+                        Some(trace) => trace.last(count).for_each(|entry| println!("{entry}")),
+                        None => println!("Tracing is off"),
+                    }
+                }
+                _ => println!("Usage: trace on [file] | off | show [count]"),
+            },
+            "rom" => match args.get(1).map(|policy| RomArg::from_str(policy, true)) {
+                Some(Ok(policy)) => state.memory.rom_policy = policy.into(),
+                Some(Err(error)) => println!("{error}"),
+                None => {
+                    let policy = RomArg::from(state.memory.rom_policy).to_possible_value();
+                    println!("ROM policy is {}", policy.unwrap().get_name());
+                }
+            },
             "interpret" | "i" => {
-                let dcl_file = args.get(1).unwrap();
-                let offset = if let Some(offset) = args.get(2) {
-                    usize::from_str_radix(offset, 10).unwrap()
-                } else {
-                    0
+                let Some((program, offset, format)) = program_arguments(&args) else {
+                    continue;
                 };
 
-                let machine_code =
-                    display_compilation(Path::new(dcl_file), offset, false)?.unwrap();
-
-                state.memory.store_array(offset, &machine_code)
+                match load_program(Path::new(program), offset, format) {
+                    Ok(Some(Program {
+                        binary,
+                        compile_info,
+                    })) => {
+                        store_program(&mut state, offset, &binary);
+                        if let Some(compile_info) = compile_info {
+                            state.checks = compile_info.debug_info.checks;
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(error) => println!("Could not load `{program}`: {error:?}"),
+                }
             }
-            "dump" | "d" => {
-                println!("{}", bold!("Registers:"));
-                println!(
-                    "- A = {:0>6b}\n- B = {:0>6b}\n- C = {:0>6b}",
-                    state.a, state.b, state.c
-                );
-                println!(
-                    "{} {}",
-                    bold!("Program Counter:"),
-                    tuple_as_usize(state.program_counter.as_tuple())
-                )
+            "load" | "l" => {
+                let Some((binary_file, offset, format)) = program_arguments(&args) else {
+                    continue;
+                };
+
+                match fs::read(binary_file) {
+                    Ok(bytes) => match image::decode(&bytes, format) {
+                        Ok(binary) => store_program(&mut state, offset, &binary),
+                        Err(error) => println!("Could not decode `{binary_file}`: {error:?}"),
+                    },
+                    Err(error) => println!("Could not read `{binary_file}`: {error}"),
+                }
             }
+            "dump" | "d" => print_state(&state),
             "help" | "h" => println!(about!(commands)),
             "quit" | "q" => break,
             "" => continue,
@@ -187,30 +695,318 @@ pub fn emulation_repl() -> Result<(), Error> {
     Ok(())
 }
 
+/// The `<path> [offset] [padded|packed]` arguments of `interpret` and `load`, or `None` once a
+/// missing or malformed one has been reported.
+fn program_arguments<'a>(args: &[&'a str]) -> Option<(&'a str, usize, ImageFormat)> {
+    let Some(path) = args.get(1) else {
+        println!("Missing program path");
+        return None;
+    };
+    let offset = match args.get(2).map(|offset| offset.parse::<usize>()) {
+        Some(Ok(offset)) => offset,
+        Some(Err(error)) => {
+            println!("{error}");
+            return None;
+        }
+        None => 0,
+    };
+    let format = match args
+        .get(3)
+        .map(|format| ImageFormat::from_str(format, true))
+    {
+        Some(Ok(format)) => format,
+        Some(Err(error)) => {
+            println!("{error}");
+            return None;
+        }
+        None => ImageFormat::default(),
+    };
+    Some((path, offset, format))
+}
+
+fn new_state() -> InteractiveState {
+    let mut state = InteractiveState::new();
+    state.trap_handler = Some(host_trap_handler());
+    state
+}
+
+/// The host services requested with `TRP`, selected by the value in `A`:
+/// - `0` print `C` as a character
+/// - `1` print `C` as a number
+/// - `2` read a character into `C`
+fn host_trap_handler() -> TrapHandler {
+    Box::new(|state| match state.a.value() {
+        0 => {
+            print!("{}", decode_character(&state.c));
+            let _ = stdout().flush();
+        }
+        1 => println!("{}", state.c),
+        2 => {
+            let mut line = String::new();
+            let _ = stdin().lock().read_line(&mut line);
+            let character = line.chars().next().unwrap_or(' ').to_ascii_uppercase();
+            state.c = encode_character(&character).copied().unwrap_or_default();
+        }
+        _ => (),
+    })
+}
+
+fn store_program(state: &mut InteractiveState, offset: usize, binary: &[u6]) {
+    match state.memory.store_array(offset, binary) {
+        Ok(()) => {
+            // Undoing past a load would leave the old program's steps applied to the new one.
+            if let Some(history) = &state.history {
+                state.history = Some(History::new(history.capacity()));
+            }
+            state.checks.clear();
+            println!("Loaded {} words at {offset}", binary.len())
+        }
+        Err(error) => println!("{} {error}", "error:".red().bold()),
+    }
+}
+
+fn report_fault(state: &InteractiveState, error: ExecutionError) {
+    println!(
+        "{} {error} at PC {}",
+        "fault:".red().bold(),
+        tuple_as_usize(state.program_counter.as_tuple())
+    );
+}
+
+fn print_state(state: &InteractiveState) {
+    println!("{}", bold!("Registers:"));
+    println!(
+        "- A = {:0>6b}\n- B = {:0>6b}\n- C = {:0>6b}",
+        state.a, state.b, state.c
+    );
+    println!(
+        "{} {}",
+        bold!("Program Counter:"),
+        tuple_as_usize(state.program_counter.as_tuple())
+    );
+    println!("{} {}", bold!("Cycles:"), state.cycles)
+}
+
+/// Run every annotated program under `path` and print a report, returning true if all passed.
+fn run_tests(path: &Path, cycles: u64) -> Result<bool, Error> {
+    let word = |value: u6| format!("{value:0>6b} ({value} {:?})", decode_character(&value));
+    let (mut passed, mut failures) = (0, Vec::new());
+    for file in testing::discover(path)? {
+        // One unreadable or faulting program is reported like a failure, the others still run.
+        let result = fs::read_to_string(&file)
+            .map_err(|error| error.to_string())
+            .and_then(|source| {
+                let source = source.to_uppercase();
+                let expectations = testing::expectations(&source)?;
+                if !testing::is_test(&source, &expectations) {
+                    return Ok(None);
+                }
+                testing::run(&source, &expectations, cycles).map(Some)
+            });
+
+        let name = file.display();
+        match result {
+            Ok(None) => continue,
+            Ok(Some(mismatches)) if mismatches.is_empty() => {
+                println!("test {name} ... {}", "ok".green());
+                passed += 1;
+            }
+            Ok(Some(mismatches)) => {
+                println!("test {name} ... {}", "FAILED".red());
+                let mut report = String::new();
+                for mismatch in mismatches {
+                    report += &format!(
+                        "line {}: RESULT {}\n{}\n{}\n",
+                        mismatch.expectation.line,
+                        mismatch.expectation.target,
+                        format!("- {}", word(mismatch.expected)).red(),
+                        format!("+ {}", word(mismatch.actual)).green(),
+                    );
+                }
+                failures.push((name.to_string(), report));
+            }
+            Err(error) => {
+                println!("test {name} ... {} {error}", "error:".red().bold());
+                failures.push((name.to_string(), format!("{error}\n")));
+            }
+        }
+    }
+
+    if !failures.is_empty() {
+        println!("\nfailures:");
+        for (name, report) in &failures {
+            print!("\n---- {name} ----\n{report}");
+        }
+    }
+    let result = if failures.is_empty() {
+        "ok".green()
+    } else {
+        "FAILED".red()
+    };
+    println!(
+        "\ntest result: {result}. {passed} passed; {} failed",
+        failures.len()
+    );
+    Ok(failures.is_empty())
+}
+
+fn print_profile(profile: &Profile, symbol_table: &HashMap<Arc<str>, u12>, debug_info: &DebugInfo) {
+    println!("{}", bold!("Profile:"));
+    println!("{:>12} {:>12}  Label", "Cycles", "Executions");
+    for (label, count) in profile.by_label(symbol_table) {
+        println!("{:>12} {:>12}  {label}", count.cycles, count.executions);
+    }
+
+    let lines = profile.by_line(debug_info);
+    if !lines.is_empty() {
+        println!("{:>12} {:>12}  Line", "Cycles", "Executions");
+        for (line, count) in lines {
+            println!("{:>12} {:>12}  {line}", count.cycles, count.executions);
+        }
+    }
+}
+
+/// How often `--watch` checks whether a file changed.
+const WATCH_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Run `action` once, or with `watch` again after every change to `path` until interrupted.
+///
+/// While watching, errors are printed instead of ending the loop.
+fn repeat_on_change(
+    path: &Path,
+    watch: bool,
+    mut action: impl FnMut() -> Result<(), Error>,
+) -> Result<(), Error> {
+    if !watch {
+        return action();
+    }
+
+    let modified = || {
+        fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    };
+    let mut last = modified();
+    loop {
+        if let Err(error) = action() {
+            println!("{} {error:?}", format!("{}:", "error".red()).bold());
+        }
+        println!(
+            "    {} `{}` for changes",
+            "Watching".cyan().bold(),
+            path.display()
+        );
+
+        // Editors may replace the file while saving, so wait until it exists again.
+        while modified().is_none() || modified() == last {
+            sleep(WATCH_INTERVAL);
+        }
+        last = modified();
+    }
+}
+
+/// A program ready to be stored in memory.
+struct Program {
+    binary: Vec<u6>,
+    /// Labels and statements, only known when the program was compiled from source.
+    compile_info: Option<CompileInfo>,
+}
+
+/// Compile a `.dcl` source file or read a binary in the given format.
+fn load_program(path: &Path, offset: usize, format: ImageFormat) -> Result<Option<Program>, Error> {
+    if path.extension().is_some_and(|extension| extension == "dcl") {
+        Ok(
+            display_compilation(path, offset, false, &LintLevels::default())?.map(|compile_info| {
+                Program {
+                    binary: compile_info.binary.clone(),
+                    compile_info: Some(compile_info),
+                }
+            }),
+        )
+    } else {
+        Ok(Some(Program {
+            binary: image::decode(&fs::read(path)?, format)?,
+            compile_info: None,
+        }))
+    }
+}
+
 fn display_compilation(
     source: &Path,
     offset: usize,
     quiet: bool,
-) -> Result<Option<Vec<u6>>, std::io::Error> {
+    lints: &LintLevels,
+) -> Result<Option<CompileInfo>, std::io::Error> {
+    let (absolute, code, code_uppercase) = read_source(source)?;
+    let mut compile_info = compile_to_binary(&code_uppercase, u12::new(offset as u16));
+    let warnings = lint(&code_uppercase, &compile_info.tokens, lints);
+    compile_info.diagnostics.extend(warnings);
+
+    Ok(display_diagnostics(
+        source,
+        &absolute,
+        &code,
+        compile_info.diagnostics.clone(),
+        compile_info.duration,
+        quiet,
+    )
+    .then_some(compile_info))
+}
+
+fn display_object_compilation(
+    source: &Path,
+    quiet: bool,
+    lints: &LintLevels,
+) -> Result<Option<Object>, std::io::Error> {
+    let (absolute, code, code_uppercase) = read_source(source)?;
+    let mut object_info = compile_to_object(&code_uppercase);
+    let tokens: Vec<_> = Cursor::new(&code_uppercase).tokenize().collect();
+    object_info
+        .diagnostics
+        .extend(lint(&code_uppercase, &tokens, lints));
+
+    Ok(display_diagnostics(
+        source,
+        &absolute,
+        &code,
+        object_info.diagnostics,
+        object_info.duration,
+        quiet,
+    )
+    .then_some(object_info.object))
+}
+
+/// Returns the absolute path, the original source, and the source used for parsing.
+fn read_source(source: &Path) -> Result<(String, String, String), std::io::Error> {
     let absolute = fs::canonicalize(source)?.to_string_lossy().into_owned();
     println!("   {} `{absolute}`", "Compiling".green().bold(),);
 
-    let code = fs::read_to_string(&source)?;
+    let code = fs::read_to_string(source)?;
     // This makes parsing case independent; the original code is saved for diagnostics
     let code_uppercase = code.to_uppercase();
+    Ok((absolute, code, code_uppercase))
+}
 
-    let compile_info = compile_to_binary(&code_uppercase, u12::new(offset as u16));
-
-    let log_level = quiet
-        .then_some(DiagLevel::Fatal)
-        .unwrap_or(DiagLevel::Warning);
+/// Print diagnostics and a summary, returning true if there were no fatal errors.
+fn display_diagnostics(
+    source: &Path,
+    absolute: &str,
+    code: &str,
+    diagnostics: Vec<Diagnostic>,
+    duration: Duration,
+    quiet: bool,
+) -> bool {
+    let log_level = if quiet {
+        DiagLevel::Fatal
+    } else {
+        DiagLevel::Warning
+    };
     let (mut errors, mut warnings) = (0, 0);
-    compile_info
-        .diagnostics
+    diagnostics
         .into_iter()
         .filter(|diag| diag.level <= log_level)
         .for_each(|diag| {
-            diag.emit(&code, source);
+            diag.emit(code, source);
             match diag.level {
                 DiagLevel::Fatal => errors += 1,
                 DiagLevel::Warning => warnings += 1,
@@ -225,13 +1021,13 @@ fn display_compilation(
         )
     }
 
-    Ok(if errors == 0 {
+    if errors == 0 {
         println!(
             "    {} `{absolute}` in {:?}",
             "Finished".green().bold(),
-            compile_info.duration,
+            duration,
         );
-        Some(compile_info.binary)
+        true
     } else {
         let error_plural = if errors > 1 { "errors" } else { "error" };
         println!(
@@ -243,6 +1039,6 @@ fn display_compilation(
                 "".to_string()
             }
         );
-        None
-    })
+        false
+    }
 }
@@ -0,0 +1,335 @@
+use std::{
+    collections::HashMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use arbitrary_int::{u12, u6};
+
+use crate::{
+    compilation::{
+        compile_to_binary,
+        diagnostic::DiagKind,
+        ir::{AddressTuple, Either, Ir},
+        lexer::Cursor,
+        parser::Parser,
+        tokens::{Keyword, TokenKind},
+        DiagLevel,
+    },
+    emulation::{ExecutionError, InteractiveState},
+    utils::tuple_as_usize,
+};
+
+/// How many cycles a test may run before it is stopped, unless `--cycles` says otherwise.
+pub const CYCLE_LIMIT: u64 = 1_000_000;
+
+/// What an expectation reads once the program halts.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Target {
+    Register(char),
+    /// The word at a constant address, e.g. `MEM[OUTPUT]` or `MEM[0 12]`.
+    Memory(String),
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Register(register) => write!(f, "{register}"),
+            Self::Memory(address) => write!(f, "MEM[{address}]"),
+        }
+    }
+}
+
+/// A `# RESULT <target> = <value>` comment; the value is anything `SET` accepts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expectation {
+    pub line: usize,
+    pub target: Target,
+    pub value: String,
+}
+
+/// An expectation that did not hold.
+#[derive(Debug, PartialEq)]
+pub struct Mismatch {
+    pub expectation: Expectation,
+    pub expected: u6,
+    pub actual: u6,
+}
+
+/// `.dcl` files in `path`, or `path` itself if it is a file, in a stable order.
+pub fn discover(path: &Path) -> io::Result<Vec<PathBuf>> {
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut files = Vec::new();
+    for entry in fs::read_dir(path)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            files.extend(discover(&path)?);
+        } else if path.extension().is_some_and(|extension| extension == "dcl") {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// The `# RESULT` comments in the uppercased `source`.
+pub fn expectations(source: &str) -> Result<Vec<Expectation>, String> {
+    Cursor::new(source)
+        .tokenize()
+        .filter(|token| token.kind == TokenKind::LineComment)
+        .filter_map(|token| {
+            let comment = source[token.span.as_range()].trim_start_matches('#').trim();
+            let line = source[..token.span.start].matches('\n').count() + 1;
+            let annotation = comment.strip_prefix("RESULT ")?;
+            Some(
+                parse_expectation(annotation)
+                    .map(|(target, value)| Expectation {
+                        line,
+                        target,
+                        value,
+                    })
+                    .map_err(|error| format!("line {line}: {error}")),
+            )
+        })
+        .collect()
+}
+
+/// Whether the uppercased `source` has anything for `run` to check: `# RESULT` comments, or
+/// `ASSERT` and `EXPECT` directives.
+pub fn is_test(source: &str, expectations: &[Expectation]) -> bool {
+    !expectations.is_empty()
+        || Cursor::new(source).tokenize().any(|token| {
+            matches!(
+                token.kind,
+                TokenKind::Keyword(Keyword::Assert | Keyword::Expect)
+            )
+        })
+}
+
+fn parse_expectation(annotation: &str) -> Result<(Target, String), String> {
+    let Some((target, value)) = annotation.split_once('=') else {
+        return Err(format!(
+            "expected `<target> = <value>` found `{annotation}`"
+        ));
+    };
+    let target = match target.trim() {
+        register @ ("A" | "B" | "C") => Target::Register(register.chars().next().unwrap()),
+        memory => match memory
+            .strip_prefix("MEM[")
+            .and_then(|address| address.strip_suffix(']'))
+        {
+            Some(address) => Target::Memory(address.trim().to_string()),
+            None => {
+                return Err(format!(
+                    "expected `A`, `B`, `C` or `MEM[address]` found `{memory}`"
+                ))
+            }
+        },
+    };
+    Ok((target, value.trim().to_string()))
+}
+
+/// Run the uppercased `source` from address zero until it halts, then check `expectations`.
+///
+/// `TRP` has no host to service it, and a program that is still running after `cycle_limit`
+/// cycles is an error like one that doesn't compile or faults.
+pub fn run(
+    source: &str,
+    expectations: &[Expectation],
+    cycle_limit: u64,
+) -> Result<Vec<Mismatch>, String> {
+    let compile_info = compile_to_binary(source, u12::new(0));
+    if let Some(diagnostic) = compile_info
+        .diagnostics
+        .iter()
+        .find(|diagnostic| diagnostic.level == DiagLevel::Fatal)
+    {
+        return Err(format!(
+            "does not compile, line {}: {}",
+            diagnostic.line(source),
+            diagnostic.kind.help()
+        ));
+    }
+
+    let mut state = InteractiveState::new();
+    state
+        .memory
+        .store_array(0, &compile_info.binary)
+        .map_err(|error| error.to_string())?;
+    state.checks = compile_info.debug_info.checks.clone();
+    loop {
+        if let Some((check, left, right)) = state.failed_check() {
+            return Err(format!(
+                "line {}: `{}` failed. {}",
+                check.line,
+                &source[check.span.as_range()],
+                DiagKind::FailedCheck { left, right }.help()
+            ));
+        }
+        if state.is_halt().map_err(|error| fault(&state, error))? {
+            break;
+        }
+        if state.cycles >= cycle_limit {
+            return Err(format!("did not halt within {cycle_limit} cycles"));
+        }
+        state
+            .consume_instruction()
+            .map_err(|error| fault(&state, error))?;
+    }
+
+    let symbol_table = &compile_info.symbol_table;
+    let mut mismatches = Vec::new();
+    for expectation in expectations {
+        let error = |error: String| format!("line {}: {error}", expectation.line);
+        let expected = evaluate(&expectation.value, symbol_table).map_err(error)?;
+        let actual = match &expectation.target {
+            Target::Register('A') => state.a,
+            Target::Register('B') => state.b,
+            Target::Register(_) => state.c,
+            Target::Memory(address) => {
+                let address = resolve_address(address, symbol_table).map_err(error)?;
+                state
+                    .memory
+                    .read(address)
+                    .map_err(|_| error(format!("`{}` is unmapped", expectation.target)))?
+            }
+        };
+        if actual != expected {
+            mismatches.push(Mismatch {
+                expectation: expectation.clone(),
+                expected,
+                actual,
+            });
+        }
+    }
+    Ok(mismatches)
+}
+
+fn fault(state: &InteractiveState, error: ExecutionError) -> String {
+    let address = tuple_as_usize(state.program_counter.as_tuple());
+    format!("{error} at PC {address}")
+}
+
+/// Parse `snippet` as a single statement, returning its IR.
+fn parse(snippet: &str) -> Result<Ir, String> {
+    let result = Parser::from(snippet).parse();
+    if let Some(diagnostic) = result.diagnostics.first() {
+        return Err(diagnostic.kind.help());
+    }
+    result
+        .ir
+        .into_iter()
+        .next()
+        .ok_or_else(|| "missing value".to_string())
+}
+
+fn evaluate(value: &str, symbol_table: &HashMap<Arc<str>, u12>) -> Result<u6, String> {
+    match parse(&format!("SET {value}"))? {
+        Ir::Set(immediate) => immediate
+            .flatten(symbol_table)
+            .map_err(|_| format!("`{value}` uses an undefined label")),
+        _ => unreachable!(),
+    }
+}
+
+fn resolve_address(
+    address: &str,
+    symbol_table: &HashMap<Arc<str>, u12>,
+) -> Result<(u6, u6), String> {
+    match parse(&format!("LOD {address}"))? {
+        Ir::Lod(AddressTuple(Either::Immediate(high), Either::Immediate(low))) => {
+            let undefined = |_| format!("`{address}` uses an undefined label");
+            Ok((
+                high.flatten(symbol_table).map_err(undefined)?,
+                low.flatten(symbol_table).map_err(undefined)?,
+            ))
+        }
+        _ => Err(format!("`{address}` is not a constant address")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test(source: &str) -> Result<Vec<Mismatch>, String> {
+        let source = source.to_uppercase();
+        run(&source, &expectations(&source)?, 1000)
+    }
+
+    #[test]
+    fn test_expectations() {
+        let source = "MOV A 5 # RESULT A = 5\n# RESULT MEM[OUTPUT] = 'X'\n# RESULTS nothing\nHLT";
+        assert_eq!(
+            expectations(source).unwrap(),
+            vec![
+                Expectation {
+                    line: 1,
+                    target: Target::Register('A'),
+                    value: "5".to_string(),
+                },
+                Expectation {
+                    line: 2,
+                    target: Target::Memory("OUTPUT".to_string()),
+                    value: "'X'".to_string(),
+                },
+            ]
+        );
+        assert_eq!(
+            expectations("# RESULT D = 1"),
+            Err("line 1: expected `A`, `B`, `C` or `MEM[address]` found `D`".to_string())
+        );
+    }
+
+    #[test]
+    fn test_is_test() {
+        assert!(is_test("ASSERT [A == 0]\nHLT", &[]));
+        assert!(!is_test("NOP # ASSERT\nHLT", &[]));
+    }
+
+    #[test]
+    fn test_run_pass_and_fail() {
+        let source = "MOV A 5\nMOV C 'x'\nSTO OUTPUT\nHLT\nLAB OUTPUT\nSET 0\n\
+                      # RESULT A = 5\n# RESULT MEM[OUTPUT] = 'X'\n# RESULT MEM[OUTPUT:0 OUTPUT:1] = 'X'";
+        assert_eq!(test(source), Ok(vec![]));
+
+        let mismatches = test("MOV B 3\nHLT # RESULT B = 0b100").unwrap();
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].expectation.target.to_string(), "B");
+        assert_eq!(
+            (mismatches[0].expected, mismatches[0].actual),
+            (u6::new(4), u6::new(3))
+        );
+    }
+
+    #[test]
+    fn test_run_errors() {
+        assert_eq!(
+            test("LAB LOOP\nPC LOOP # RESULT A = 0"),
+            Err("did not halt within 1000 cycles".to_string())
+        );
+        assert_eq!(
+            test("HLT # RESULT MEM[MISSING] = 0"),
+            Err("line 1: `MISSING` uses an undefined label".to_string())
+        );
+        assert_eq!(
+            test("HLT # RESULT MEM[A B] = 0"),
+            Err("line 1: `A B` is not a constant address".to_string())
+        );
+        assert_eq!(
+            test("PC 0x3D 0x00 # RESULT A = 0"),
+            Err("read from unmapped address 0xF40 at PC 3904".to_string())
+        );
+        assert!(test("MOV Q 1 # RESULT A = 0")
+            .unwrap_err()
+            .starts_with("does not compile, line 1"));
+        assert_eq!(
+            test("MOV A 5\nASSERT [A == 4]\nHLT"),
+            Err("line 2: `ASSERT [A == 4]` failed. The left side is `000101` and the right side is `000100`".to_string())
+        );
+    }
+}
@@ -0,0 +1,317 @@
+use crate::{compilation::compile_to_binary, emulation::InteractiveState};
+use arbitrary_int::{u12, u6};
+
+/// Run `source` once for every pair of 6-bit operands and compare the result register.
+fn assert_exhaustive(
+    source: &str,
+    load: impl Fn(&mut InteractiveState, u6, u6),
+    result: impl Fn(&InteractiveState) -> u6,
+    expected: impl Fn(u8, u8) -> u8,
+) {
+    let machine_code_result = compile_to_binary(source, u12::new(0));
+    assert_eq!(machine_code_result.diagnostics.len(), 0);
+
+    for left in 0..64 {
+        for right in 0..64 {
+            let mut state = InteractiveState::new();
+            state
+                .memory
+                .store_array(0, &machine_code_result.binary)
+                .unwrap();
+            load(&mut state, u6::new(left), u6::new(right));
+            state.consume_until_halt().unwrap();
+            assert_eq!(
+                result(&state),
+                u6::new(expected(left, right)),
+                "`{source}` with ({left}, {right})"
+            );
+        }
+    }
+}
+
+/// Same as [`assert_exhaustive`] but the right operand is compiled in as an immediate.
+fn assert_exhaustive_immediate(
+    keyword: &str,
+    register: char,
+    load: impl Fn(&mut InteractiveState, u6),
+    result: impl Fn(&InteractiveState) -> u6,
+    expected: impl Fn(u8, u8) -> u8,
+) {
+    for right in 0..64 {
+        let source = format!("{keyword} {register} {right}\nHLT");
+        let machine_code_result = compile_to_binary(&source, u12::new(0));
+        assert_eq!(machine_code_result.diagnostics.len(), 0);
+
+        for left in 0..64 {
+            let mut state = InteractiveState::new();
+            state
+                .memory
+                .store_array(0, &machine_code_result.binary)
+                .unwrap();
+            load(&mut state, u6::new(left));
+            state.consume_until_halt().unwrap();
+            assert_eq!(
+                result(&state),
+                u6::new(expected(left, right)),
+                "`{keyword} {register} {right}` with {register} = {left}"
+            );
+        }
+    }
+}
+
+macro_rules! test_arithmetic_builder {
+    ($keyword:expr, $expected:expr) => {
+        #[test]
+        fn a_and_b() {
+            assert_exhaustive(
+                concat!($keyword, " A B\nHLT"),
+                |state, left, right| (state.a, state.b) = (left, right),
+                |state| state.a,
+                $expected,
+            );
+        }
+
+        #[test]
+        fn c_and_a() {
+            assert_exhaustive(
+                concat!($keyword, " C A\nHLT"),
+                |state, left, right| (state.c, state.a) = (left, right),
+                |state| state.c,
+                $expected,
+            );
+        }
+
+        #[test]
+        fn b_and_c() {
+            assert_exhaustive(
+                concat!($keyword, " B C\nHLT"),
+                |state, left, right| (state.b, state.c) = (left, right),
+                |state| state.b,
+                $expected,
+            );
+        }
+
+        #[test]
+        fn a_and_immediate() {
+            assert_exhaustive_immediate(
+                $keyword,
+                'A',
+                |state, left| state.a = left,
+                |state| state.a,
+                $expected,
+            );
+        }
+
+        #[test]
+        fn c_and_immediate() {
+            assert_exhaustive_immediate(
+                $keyword,
+                'C',
+                |state, left| state.c = left,
+                |state| state.c,
+                $expected,
+            );
+        }
+
+        #[test]
+        fn b_and_itself() {
+            let source = concat!($keyword, " B B\nHLT");
+            let machine_code_result = compile_to_binary(source, u12::new(0));
+            assert_eq!(machine_code_result.diagnostics.len(), 0);
+
+            for value in 0..64 {
+                let mut state = InteractiveState::new();
+                state
+                    .memory
+                    .store_array(0, &machine_code_result.binary)
+                    .unwrap();
+                state.b = u6::new(value);
+                state.consume_until_halt().unwrap();
+                assert_eq!(state.b, u6::new($expected(value, value)), "{value}");
+            }
+        }
+    };
+}
+
+mod add {
+    use super::*;
+
+    fn expected(left: u8, right: u8) -> u8 {
+        left.wrapping_add(right) & 0b111111
+    }
+
+    test_arithmetic_builder!("ADD", expected);
+}
+
+mod sub {
+    use super::*;
+
+    fn expected(left: u8, right: u8) -> u8 {
+        left.wrapping_sub(right) & 0b111111
+    }
+
+    test_arithmetic_builder!("SUB", expected);
+}
+
+mod mul {
+    use super::*;
+
+    fn expected(left: u8, right: u8) -> u8 {
+        left.wrapping_mul(right) & 0b111111
+    }
+
+    test_arithmetic_builder!("MUL", expected);
+}
+
+mod div {
+    use super::*;
+
+    fn expected(left: u8, right: u8) -> u8 {
+        left.checked_div(right).unwrap_or(0b111111)
+    }
+
+    test_arithmetic_builder!("DIV", expected);
+}
+
+mod modulo {
+    use super::*;
+
+    fn expected(left: u8, right: u8) -> u8 {
+        left.checked_rem(right).unwrap_or(left)
+    }
+
+    test_arithmetic_builder!("MOD", expected);
+}
+
+/// Run `source` for every pair of operands and incoming carry; the carry lives in `CARRY` or `C`.
+fn assert_exhaustive_with_carry(keyword: &str, expected: impl Fn(u8, u8, u8) -> (u8, u8)) {
+    for carry_in in 0..2 {
+        let source = format!("{keyword} A B CARRY\nHLT\nLAB CARRY\nSET {carry_in}");
+        let machine_code_result = compile_to_binary(&source, u12::new(0));
+        assert_eq!(machine_code_result.diagnostics.len(), 0);
+        let carry = machine_code_result.symbol_table["CARRY"].value() as usize;
+
+        for left in 0..64 {
+            for right in 0..64 {
+                let mut state = InteractiveState::new();
+                state
+                    .memory
+                    .store_array(0, &machine_code_result.binary)
+                    .unwrap();
+                (state.a, state.b) = (u6::new(left), u6::new(right));
+                state.consume_until_halt().unwrap();
+                let (result, carry_out) = expected(left, right, carry_in);
+                assert_eq!(
+                    (state.a, state.memory.ram[carry]),
+                    (u6::new(result), u6::new(carry_out)),
+                    "`{keyword} A B CARRY` with ({left}, {right}, {carry_in})"
+                );
+            }
+        }
+    }
+
+    let source = format!("{keyword} A B C\nHLT");
+    let machine_code_result = compile_to_binary(&source, u12::new(0));
+    assert_eq!(machine_code_result.diagnostics.len(), 0);
+
+    for carry_in in 0..2 {
+        for left in 0..64 {
+            for right in 0..64 {
+                let mut state = InteractiveState::new();
+                state
+                    .memory
+                    .store_array(0, &machine_code_result.binary)
+                    .unwrap();
+                (state.a, state.b, state.c) = (u6::new(left), u6::new(right), u6::new(carry_in));
+                state.consume_until_halt().unwrap();
+                let (result, carry_out) = expected(left, right, carry_in);
+                assert_eq!(
+                    (state.a, state.c),
+                    (u6::new(result), u6::new(carry_out)),
+                    "`{keyword} A B C` with ({left}, {right}, {carry_in})"
+                );
+            }
+        }
+    }
+}
+
+mod adc {
+    use super::*;
+
+    #[test]
+    fn exhaustive() {
+        assert_exhaustive_with_carry("ADC", |left, right, carry| {
+            let sum = left + right + carry;
+            (sum & 0b111111, sum >> 6)
+        });
+    }
+}
+
+mod sbc {
+    use super::*;
+
+    #[test]
+    fn exhaustive() {
+        assert_exhaustive_with_carry("SBC", |left, right, borrow| {
+            let difference = left.wrapping_sub(right).wrapping_sub(borrow);
+            (difference & 0b111111, (left < right + borrow) as u8)
+        });
+    }
+}
+
+const WIDE_VALUES: [u16; 12] = [0, 1, 2, 62, 63, 64, 65, 1234, 2047, 2048, 4094, 4095];
+
+fn store_wide(state: &mut InteractiveState, address: usize, value: u16) {
+    state.memory.ram[address] = u6::new((value & 0b111111) as u8);
+    state.memory.ram[address + 1] = u6::new((value >> 6) as u8);
+}
+
+fn load_wide(state: &InteractiveState, address: usize) -> u16 {
+    u16::from(state.memory.ram[address]) | (u16::from(state.memory.ram[address + 1]) << 6)
+}
+
+#[test]
+fn add12() {
+    let source = "ADD12 X Y\nHLT\nLAB X\nSET 0\nSET 0\nLAB Y\nSET 0\nSET 0";
+    let machine_code_result = compile_to_binary(source, u12::new(0));
+    assert_eq!(machine_code_result.diagnostics.len(), 0);
+    let x = machine_code_result.symbol_table["X"].value() as usize;
+    let y = machine_code_result.symbol_table["Y"].value() as usize;
+
+    for left in WIDE_VALUES {
+        for right in WIDE_VALUES {
+            let mut state = InteractiveState::new();
+            state
+                .memory
+                .store_array(0, &machine_code_result.binary)
+                .unwrap();
+            store_wide(&mut state, x, left);
+            store_wide(&mut state, y, right);
+            state.consume_until_halt().unwrap();
+            assert_eq!(
+                (load_wide(&state, x), load_wide(&state, y)),
+                ((left + right) & 0xFFF, right),
+                "{left} + {right}"
+            );
+        }
+    }
+}
+
+#[test]
+fn inc12() {
+    let source = "INC12 X\nHLT\nLAB X\nSET 0\nSET 0";
+    let machine_code_result = compile_to_binary(source, u12::new(0));
+    assert_eq!(machine_code_result.diagnostics.len(), 0);
+    let x = machine_code_result.symbol_table["X"].value() as usize;
+
+    for value in 0..0x1000 {
+        let mut state = InteractiveState::new();
+        state
+            .memory
+            .store_array(0, &machine_code_result.binary)
+            .unwrap();
+        store_wide(&mut state, x, value);
+        state.consume_until_halt().unwrap();
+        assert_eq!(load_wide(&state, x), (value + 1) & 0xFFF, "{value} + 1");
+    }
+}
@@ -14,7 +14,7 @@ macro_rules! test_bitwise_builder {
         test_builder!(
             $test_name,
             concat!($keyword, "\nHLT"),
-            |$mutation_state| {$mutation_closure $mutation_state.consume_until_halt();},
+            |$mutation_state| {$mutation_closure $mutation_state.consume_until_halt().unwrap();},
             |state, _machine_code_result| {
                 $($(assert_eq!(state.$reg, $value))?);*
             }
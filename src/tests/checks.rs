@@ -0,0 +1,99 @@
+use crate::{
+    compilation::{compile_to_binary, CompileInfo},
+    emulation::{ExecutionError, InteractiveState},
+    test_builder,
+};
+use arbitrary_int::{u12, u6};
+
+test_builder!(
+    checks_pass,
+    "MOV A 5\nASSERT [A == 5]\nEXPECT A = 5\nMOV C 'X'\nSTO DATA\nEXPECT MEM[DATA] = 'X'\nHLT\nLAB DATA\nSET 0",
+    |state| {
+        assert_eq!(state.checks.len(), 3);
+        state.consume_until_halt().unwrap();
+    },
+    |state, _machine_code_result| {
+        assert_eq!(state.a, u6::new(5));
+    }
+);
+
+test_builder!(
+    checks_in_a_branch_not_taken,
+    "MOV A 0\nIF [A == 1]\n    MOV B 2\n    ASSERT [B == 2]\nEND\nHLT",
+    |state| {
+        // The label closing the `IF` is jumped to, which doesn't run the check in its body.
+        state.consume_until_halt().unwrap();
+    },
+    |state, _machine_code_result| {
+        assert_eq!(state.is_halt(), Ok(true));
+    }
+);
+
+test_builder!(
+    checks_before_a_label,
+    "EXPECT MEM[FLAG] = 0\nLAB AGAIN\nLOD FLAG\nNOT C\nSTO FLAG\nLIH [C != 0] AGAIN\nHLT\nLAB FLAG\nSET 0",
+    |state| {
+        // Jumping back to `AGAIN` doesn't run the check above it.
+        state.consume_until_halt().unwrap();
+    },
+    |state, machine_code_result| {
+        let flag = machine_code_result.symbol_table["FLAG"].value() as usize;
+        assert_eq!(state.memory.ram[flag], u6::new(0));
+    }
+);
+
+test_builder!(
+    checks_after_a_block,
+    "MOV A 1\nIF [A == 1]\n    MOV B 2\nELSE\n    MOV B 3\nEND\nASSERT [B == 3]\nHLT",
+    |_state| {},
+    |state, _machine_code_result| {
+        // The `IF` body jumps over the `ELSE` to the end of the block, which is before the check.
+        let mut state = state;
+        assert_eq!(
+            state.consume_until_halt(),
+            Err(ExecutionError::FailedCheck { line: 7 })
+        );
+    }
+);
+
+test_builder!(
+    checks_ignored_without_debug_info,
+    "MOV A 5\nASSERT [A == 4]\nHLT",
+    |state| {
+        state.checks.clear();
+        state.consume_until_halt().unwrap();
+    },
+    |state, _machine_code_result| {
+        assert_eq!(state.a, u6::new(5));
+    }
+);
+
+test_builder!(
+    checks_stop_before_the_next_instruction,
+    "MOV A 5\nASSERT [A <S 4]\nMOV B 1\nHLT",
+    |_state| {},
+    |state, _machine_code_result| {
+        let mut state = state;
+        assert_eq!(
+            state.consume_until_halt(),
+            Err(ExecutionError::FailedCheck { line: 2 })
+        );
+        assert_eq!(state.b, u6::new(0));
+        let (check, left, right) = state.failed_check().unwrap();
+        assert_eq!((check.line, left, right), (2, u6::new(5), u6::new(4)));
+    }
+);
+
+test_builder!(
+    checks_before_halt,
+    "NOT A\nEXPECT A = 0\nHLT",
+    |_state| {},
+    |state, _machine_code_result| {
+        let mut state = state;
+        assert_eq!(
+            state.consume_until_halt(),
+            Err(ExecutionError::FailedCheck { line: 2 })
+        );
+        assert_eq!(state.is_halt(), Ok(true));
+    }
+);
@@ -0,0 +1,134 @@
+use crate::{
+    compilation::{compile_to_binary, diagnostic::DiagKind, CompileInfo},
+    emulation::InteractiveState,
+    test_builder,
+};
+use arbitrary_int::{u12, u6};
+
+test_builder!(
+    if_true,
+    "IF [A == 1]\nMOV B 10\nEND\nHLT",
+    |state| {
+        state.a = u6::new(1);
+        state.consume_until_halt().unwrap();
+    },
+    |state, _machine_code_result| {
+        assert_eq!(state.b, u6::new(10));
+    }
+);
+
+test_builder!(
+    if_false,
+    "IF [A == 1]\nMOV B 10\nEND\nHLT",
+    |state| {
+        state.a = u6::new(2);
+        state.consume_until_halt().unwrap();
+    },
+    |state, _machine_code_result| {
+        assert_ne!(state.b, u6::new(10));
+    }
+);
+
+test_builder!(
+    if_else_true,
+    "IF [A <S B]\nMOV C 10\nELSE\nMOV C 20\nEND\nHLT",
+    |state| {
+        state.a = u6::new(0b111111);
+        state.b = u6::new(1);
+        state.consume_until_halt().unwrap();
+    },
+    |state, _machine_code_result| {
+        assert_eq!(state.c, u6::new(10));
+    }
+);
+
+test_builder!(
+    if_else_false,
+    "IF [A <S B]\nMOV C 10\nELSE\nMOV C 20\nEND\nHLT",
+    |state| {
+        state.a = u6::new(1);
+        state.b = u6::new(0b111111);
+        state.consume_until_halt().unwrap();
+    },
+    |state, _machine_code_result| {
+        assert_eq!(state.c, u6::new(20));
+    }
+);
+
+test_builder!(
+    nested_if,
+    // Conditions clobber every register, so the value is reloaded before each one
+    "LOD VALUE\nIF [C > 2]\nLOD VALUE\nIF [C > 4]\nMOV A 3\nELSE\nMOV A 2\nEND\nELSE\nMOV A 1\nEND\nHLT\nLAB VALUE\nSET 3",
+    |state| {
+        state.consume_until_halt().unwrap();
+    },
+    |state, _machine_code_result| {
+        assert_eq!(state.a, u6::new(2));
+    }
+);
+
+test_builder!(
+    while_loop,
+    "LOD COUNT\nWHILE [C < 5]\nLOD COUNT\nADD C 1\nSTO COUNT\nEND\nHLT\nLAB COUNT\nSET 0",
+    |state| {
+        state.consume_until_halt().unwrap();
+    },
+    |state, machine_code_result| {
+        let count = machine_code_result.symbol_table["COUNT"].value() as usize;
+        assert_eq!(state.memory.ram[count], u6::new(5));
+    }
+);
+
+test_builder!(
+    while_never_entered,
+    "WHILE [A != A]\nMOV B 10\nEND\nHLT",
+    |state| {
+        state.consume_until_halt().unwrap();
+    },
+    |state, _machine_code_result| {
+        assert_ne!(state.b, u6::new(10));
+    }
+);
+
+test_builder!(
+    fibonacci_example,
+    include_str!("../../examples/fibonacci.dcl"),
+    |state| {
+        state.consume_until_halt().unwrap();
+    },
+    |state, _machine_code_result| {
+        assert_eq!(state.c, u6::new(5));
+    }
+);
+
+#[test]
+fn unterminated_blocks_point_at_their_opener() {
+    let source = "IF [A == 1]\nWHILE [B == 1]\nEND\nWHILE [C == 1]\nNOP";
+    let diagnostics = compile_to_binary(source, u12::new(0)).diagnostics;
+
+    let spans: Vec<_> = diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.kind == DiagKind::UnterminatedBlock)
+        .map(|diagnostic| &source[diagnostic.span.as_range()])
+        .collect();
+    assert_eq!(spans, vec!["IF", "WHILE"]);
+    assert_eq!(diagnostics[0].span.start, 0);
+    assert_eq!(diagnostics[1].span.start, 31);
+}
+
+#[test]
+fn unmatched_else_and_end() {
+    let diagnostics = compile_to_binary("ELSE\nEND\nWHILE [A == 1]\nELSE\nEND", u12::new(0))
+        .diagnostics
+        .into_iter()
+        .map(|diagnostic| diagnostic.kind)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        diagnostics,
+        vec![
+            DiagKind::UnexpectedElse,
+            DiagKind::UnexpectedEnd,
+            DiagKind::UnexpectedElse,
+        ]
+    );
+}
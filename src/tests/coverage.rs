@@ -0,0 +1,76 @@
+use std::path::Path;
+
+use crate::{
+    compilation::{compile_to_binary, CompileInfo},
+    emulation::{lcov, InteractiveState, Profile},
+    test_builder,
+    utils::tuple_as_usize,
+};
+use arbitrary_int::{u12, u6};
+
+test_builder!(
+    coverage_lines,
+    "NOR A 0\nPC SKIP\nNOP\nLAB SKIP\nHLT\nSET 5",
+    |state| {
+        state.profile = Some(Profile::new());
+        state.consume_until_halt().unwrap();
+    },
+    |state, machine_code_result| {
+        let report = lcov(
+            state.profile.as_ref().unwrap(),
+            Some(tuple_as_usize(state.program_counter.as_tuple())),
+            Path::new("/test.dcl"),
+            &machine_code_result.debug_info,
+        );
+        assert_eq!(
+            report,
+            "TN:\nSF:/test.dcl\nBRF:0\nBRH:0\nDA:1,1\nDA:2,1\nDA:3,0\nDA:5,1\nLF:4\nLH:3\nend_of_record\n"
+        );
+
+        // Without the halt, the `HLT` line was never reached.
+        let report = lcov(
+            state.profile.as_ref().unwrap(),
+            None,
+            Path::new("/test.dcl"),
+            &machine_code_result.debug_info,
+        );
+        assert!(report.contains("DA:5,0\n"));
+    }
+);
+
+test_builder!(
+    coverage_branches,
+    "LAB LOOP\nLOD COUNT\nADD C 1\nSTO COUNT\nLIH [C != 3] LOOP\nLOD COUNT\nIF [C == 0]\nNOP\nEND\nHLT\nLAB COUNT\nSET 0",
+    |state| {
+        state.profile = Some(Profile::new());
+        state.consume_until_halt().unwrap();
+    },
+    |state, machine_code_result| {
+        let count = machine_code_result.symbol_table["COUNT"].value() as usize;
+        assert_eq!(state.memory.ram[count], u6::new(3));
+        let report = lcov(
+            state.profile.as_ref().unwrap(),
+            Some(tuple_as_usize(state.program_counter.as_tuple())),
+            Path::new("/test.dcl"),
+            &machine_code_result.debug_info,
+        );
+        let branches: Vec<_> = report
+            .lines()
+            .filter(|line| line.starts_with("BR"))
+            .collect();
+        assert_eq!(
+            branches,
+            vec![
+                // The loop jumps back twice and falls through once
+                "BRDA:5,0,0,2",
+                "BRDA:5,0,1,1",
+                // `IF` jumps past its body when the condition is false
+                "BRDA:7,1,0,1",
+                "BRDA:7,1,1,0",
+                "BRF:4",
+                "BRH:3",
+            ]
+        );
+        assert!(report.contains("DA:8,0\n"));
+    }
+);
@@ -0,0 +1,55 @@
+use crate::{
+    compilation::{compile_to_binary, CompileInfo},
+    emulation::{History, InteractiveState},
+    test_builder,
+};
+use arbitrary_int::{u12, u6};
+
+test_builder!(
+    cycles_count_fetches,
+    "NOR A B\nNOR A 5\nSTO 0 10\nHLT",
+    |state| {
+        state.consume_until_halt().unwrap();
+    },
+    |state, _machine_code_result| {
+        // One fetch per opcode and one per immediate, the final `HLT` is never consumed
+        assert_eq!(state.cycles, 1 + 2 + 3);
+    }
+);
+
+test_builder!(
+    cycles_count_halt,
+    "HLT",
+    |state| {
+        state.consume_instruction().unwrap();
+    },
+    |state, _machine_code_result| {
+        assert_eq!(state.cycles, 1);
+    }
+);
+
+test_builder!(
+    cycles_step_back,
+    "NOR A 5\nNOP\nHLT",
+    |state| {
+        state.history = Some(History::new(16));
+        state.consume_until_halt().unwrap();
+        assert_eq!(state.cycles, 3);
+        state.step_back();
+    },
+    |state, _machine_code_result| {
+        assert_eq!(state.cycles, 2);
+    }
+);
+
+test_builder!(
+    cycles_fault,
+    "STO 0x3C 0x3E\nHLT",
+    |state| {
+        assert!(state.consume_instruction().is_err());
+    },
+    |state, _machine_code_result| {
+        assert_eq!(state.cycles, 0);
+        assert_eq!(state.program_counter.as_tuple(), (u6::new(0), u6::new(0)));
+    }
+);
@@ -1,6 +1,6 @@
 use crate::{
     compilation::{compile_to_binary, CompileInfo},
-    emulation::InteractiveState,
+    emulation::{ExecutionError, InteractiveState, Trace},
     test_builder,
 };
 use arbitrary_int::{u12, u6};
@@ -9,7 +9,7 @@ test_builder!(
     nop,
     "NOP\nHLT",
     |state| {
-        state.consume_until_halt();
+        state.consume_until_halt().unwrap();
     },
     |state, machine_code_result| {
         assert_eq!(machine_code_result.diagnostics.len(), 0);
@@ -24,7 +24,7 @@ test_builder!(
     halt,
     "HLT",
     |state| {
-        state.consume_until_halt();
+        state.consume_until_halt().unwrap();
     },
     |state, machine_code_result| {
         assert_eq!(machine_code_result.diagnostics.len(), 0);
@@ -40,10 +40,86 @@ test_builder!(
     halt_after_nop,
     "NOP\nHLT",
     |state| {
-        state.consume_until_halt();
+        state.consume_until_halt().unwrap();
     },
     |state, machine_code_result| {
         assert_eq!(machine_code_result.diagnostics.len(), 0);
         assert_eq!(state.program_counter.as_tuple(), (u6::new(0), u6::new(1)));
     }
 );
+
+test_builder!(
+    illegal_opcode,
+    "NOP\nTRP\nHLT",
+    |state| {
+        state.strict = true;
+        assert_eq!(
+            state.consume_until_halt(),
+            Err(ExecutionError::IllegalOpcode(u6::new(0b001101)))
+        );
+    },
+    |state, _machine_code_result| {
+        // The program counter stays on the faulting instruction
+        assert_eq!(state.program_counter.as_tuple(), (u6::new(0), u6::new(1)));
+    }
+);
+
+test_builder!(
+    write_to_rom,
+    "STO 0x3C 0x3E\nHLT",
+    |state| {
+        assert_eq!(
+            state.consume_instruction(),
+            Err(ExecutionError::WriteToRom((u6::new(0x3C), u6::new(0x3E))))
+        );
+    },
+    |state, _machine_code_result| {
+        assert_eq!(state.program_counter.as_tuple(), (u6::new(0), u6::new(0)));
+    }
+);
+
+test_builder!(
+    load_from_unmapped,
+    "LOD 0x3D 0x00\nHLT",
+    |state| {
+        assert_eq!(
+            state.consume_until_halt(),
+            Err(ExecutionError::UnmappedRead((u6::new(0x3D), u6::new(0x00))))
+        );
+    },
+    |state, _machine_code_result| {
+        assert_eq!(state.program_counter.as_tuple(), (u6::new(0), u6::new(0)));
+    }
+);
+
+test_builder!(
+    fetch_from_unmapped,
+    "PC 0x3D 0x00",
+    |state| {
+        state.consume_instruction().unwrap();
+        let unmapped = ExecutionError::UnmappedRead((u6::new(0x3D), u6::new(0x00)));
+        assert_eq!(state.is_halt(), Err(unmapped));
+        assert_eq!(state.consume_instruction(), Err(unmapped));
+    },
+    |state, _machine_code_result| {
+        assert_eq!(
+            state.program_counter.as_tuple(),
+            (u6::new(0x3D), u6::new(0))
+        );
+    }
+);
+
+test_builder!(
+    write_to_unmapped_rom,
+    "STO 0x3D 0x00\nHLT",
+    |state| {
+        state.trace = Some(Trace::new(16));
+        assert_eq!(
+            state.consume_instruction(),
+            Err(ExecutionError::WriteToRom((u6::new(0x3D), u6::new(0x00))))
+        );
+    },
+    |state, _machine_code_result| {
+        assert_eq!(state.program_counter.as_tuple(), (u6::new(0), u6::new(0)));
+    }
+);
@@ -0,0 +1,110 @@
+use crate::{
+    compilation::{compile_to_binary, CompileInfo},
+    emulation::{History, InteractiveState, RomPolicy},
+    test_builder,
+};
+use arbitrary_int::{u12, u6};
+
+test_builder!(
+    step_back_restores_state,
+    "NOR C 0\nSTO 0 10\nNOR A 0\nHLT",
+    |state| {
+        state.history = Some(History::new(16));
+        state.consume_until_halt().unwrap();
+        assert!(state.step_back());
+        assert!(state.step_back());
+    },
+    |state, _machine_code_result| {
+        // Back on the `STO`, before it wrote memory
+        assert_eq!(state.program_counter.as_tuple(), (u6::new(0), u6::new(2)));
+        assert_eq!(state.memory.ram[10], u6::new(0));
+        assert_eq!(state.a, u6::new(0));
+        assert_eq!(state.c, u6::new(63));
+    }
+);
+
+test_builder!(
+    step_back_to_start,
+    "NOR A 0\nNOR B 0\nHLT",
+    |state| {
+        state.history = Some(History::new(16));
+        state.consume_until_halt().unwrap();
+        while state.step_back() {}
+    },
+    |state, _machine_code_result| {
+        assert_eq!(state.program_counter.as_tuple(), (u6::new(0), u6::new(0)));
+        assert_eq!((state.a, state.b), (u6::new(0), u6::new(0)));
+        assert_eq!(state.last_instruction, None);
+    }
+);
+
+test_builder!(
+    history_capacity,
+    "NOP\nNOP\nNOP\nNOP\nHLT",
+    |state| {
+        state.history = Some(History::new(2));
+        state.consume_until_halt().unwrap();
+        assert!(state.step_back());
+        assert!(state.step_back());
+        assert!(!state.step_back());
+    },
+    |state, _machine_code_result| {
+        assert_eq!(state.history.as_ref().unwrap().len(), 0);
+        assert_eq!(state.program_counter.as_tuple(), (u6::new(0), u6::new(2)));
+    }
+);
+
+test_builder!(
+    step_back_over_interrupt,
+    "HLT\nNOP\nIRT",
+    |state| {
+        state.history = Some(History::new(16));
+        state.interrupt((u6::new(0), u6::new(1)));
+        state.consume_instruction().unwrap();
+        assert!(state.step_back());
+        assert!(state.step_back());
+    },
+    |state, _machine_code_result| {
+        assert_eq!(state.program_counter.as_tuple(), (u6::new(0), u6::new(0)));
+        assert_eq!(state.interrupt_return, None);
+        assert_eq!(state.is_halt(), Ok(true));
+    }
+);
+
+test_builder!(
+    step_back_over_rom_write,
+    "MOV C 5\nSTO 0x3D 0x00\nSTO 0x3E 0x00\nHLT",
+    |state| {
+        state.history = Some(History::new(16));
+        state.memory.rom_policy = RomPolicy::AllowAsRam;
+        state.consume_until_halt().unwrap();
+        assert!(state.step_back());
+        assert!(state.step_back());
+    },
+    |state, _machine_code_result| {
+        // Both ROM words read through to what was there before they were written as RAM
+        assert!(state.memory.read((u6::new(0x3D), u6::new(0x00))).is_err());
+        assert_eq!(
+            state.memory.read((u6::new(0x3E), u6::new(0x00))),
+            Ok(u6::new(0))
+        );
+    }
+);
+
+test_builder!(
+    step_back_over_trap,
+    "MOV A 20\nTRP\nHLT",
+    |state| {
+        state.history = Some(History::new(16));
+        state.trap_handler = Some(Box::new(|state| {
+            state.c = state.a;
+            state.memory.ram[10] = state.a;
+        }));
+        state.consume_until_halt().unwrap();
+        assert!(state.step_back());
+    },
+    |state, _machine_code_result| {
+        assert_eq!((state.a, state.c), (u6::new(20), u6::new(0)));
+        assert_eq!(state.memory.ram[10], u6::new(0));
+    }
+);
@@ -1,5 +1,5 @@
 use crate::{
-    compilation::{compile_to_binary, CompileInfo},
+    compilation::{compile_to_binary, diagnostic::DiagKind, CompileInfo},
     emulation::InteractiveState,
     test_builder,
     utils::tuple_as_u12,
@@ -10,7 +10,7 @@ test_builder!(
     pc,
     "PC 0 32",
     |state| {
-        state.consume_instruction();
+        state.consume_instruction().unwrap();
     },
     |state, _machine_code_result| {
         assert_eq!(state.program_counter.as_tuple(), (u6::new(0), u6::new(32)));
@@ -37,7 +37,7 @@ test_builder!(
     pc_to_lab,
     "PC TEST\nNOP\nLAB TEST",
     |state| {
-        state.consume_instruction();
+        state.consume_instruction().unwrap();
     },
     |state, machine_code_result| {
         assert_eq!(
@@ -46,3 +46,64 @@ test_builder!(
         );
     }
 );
+
+test_builder!(
+    local_labels,
+    "LAB FIRST\nLAB .LOOP\nNOP\nLAB SECOND\nNOP\nLAB .LOOP\nPC FIRST.LOOP",
+    |_state| {},
+    |_state, machine_code_result| {
+        assert_eq!(
+            machine_code_result.symbol_table.get("FIRST.LOOP"),
+            Some(&u12::new(0))
+        );
+        assert_eq!(
+            machine_code_result.symbol_table.get("SECOND.LOOP"),
+            Some(&u12::new(2))
+        );
+    }
+);
+
+test_builder!(
+    pc_to_local_lab,
+    "LAB MAIN\nPC .END\nNOP\nLAB .END\nHLT",
+    |state| {
+        state.consume_instruction().unwrap();
+    },
+    |state, machine_code_result| {
+        assert_eq!(
+            Some(&tuple_as_u12(state.program_counter.as_tuple())),
+            machine_code_result.symbol_table.get("MAIN.END")
+        );
+    }
+);
+
+test_builder!(
+    anonymous_labels,
+    "PC +\nLAB -\nHLT\nLAB +\nPC ++\nLAB +\nMOV A 2\nLAB +\nLAB -\nMOV B 3\nPC --",
+    |state| {
+        state.consume_until_halt().unwrap();
+    },
+    |state, _machine_code_result| {
+        assert_eq!((state.a, state.b), (u6::new(0), u6::new(3)));
+    }
+);
+
+#[test]
+fn duplicate_local_label() {
+    let diagnostics = compile_to_binary("LAB MAIN\nLAB .LOOP\nLAB .LOOP", u12::new(0)).diagnostics;
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].kind, DiagKind::DuplicateLabel);
+}
+
+#[test]
+fn undefined_anonymous_label() {
+    let diagnostics = compile_to_binary("PC -\nPC +\nNOP", u12::new(0)).diagnostics;
+    let kinds: Vec<_> = diagnostics
+        .into_iter()
+        .map(|diagnostic| diagnostic.kind)
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![DiagKind::UndefinedLabel, DiagKind::UndefinedLabel]
+    );
+}
@@ -16,7 +16,7 @@ macro_rules! test_matrix_helper {
                 state.a = u6::new($a);
                 state.b = u6::new($b);
                 state.c = u6::new($c);
-                state.consume_until_halt()
+                state.consume_until_halt().unwrap()
             },
             |state, machine_code_result| {
                 assert_eq!(
@@ -140,3 +140,111 @@ mod less_or_equal {
     use super::*;
     test_matrix_builder!("<=", [1, 1, 0]);
 }
+
+/// Interpret a 6-bit value as two's complement.
+fn signed(value: u8) -> i8 {
+    ((value << 2) as i8) >> 2
+}
+
+/// Check `LIH [A condition B]` against every pair of 6-bit operands.
+fn assert_exhaustive(condition: &str, expected: impl Fn(u8, u8) -> bool) {
+    let source = format!("LIH [A {condition} B] TEST\nHLT\nLAB TEST\nHLT");
+    let machine_code_result = compile_to_binary(&source, u12::new(0));
+    assert_eq!(machine_code_result.diagnostics.len(), 0);
+    let test = machine_code_result.symbol_table.get("TEST").cloned();
+
+    for left in 0..64 {
+        for right in 0..64 {
+            let mut state = InteractiveState::new();
+            state
+                .memory
+                .store_array(0, &machine_code_result.binary)
+                .unwrap();
+            state.a = u6::new(left);
+            state.b = u6::new(right);
+            state.consume_until_halt().unwrap();
+            assert_eq!(
+                Some(tuple_as_u12(state.program_counter.as_tuple())) == test,
+                expected(left, right),
+                "[{left} {condition} {right}]"
+            );
+        }
+    }
+}
+
+mod exhaustive {
+    use super::*;
+
+    #[test]
+    fn equal() {
+        assert_exhaustive("==", |left, right| left == right);
+    }
+
+    #[test]
+    fn not_equal() {
+        assert_exhaustive("!=", |left, right| left != right);
+    }
+
+    #[test]
+    fn greater() {
+        assert_exhaustive(">", |left, right| left > right);
+    }
+
+    #[test]
+    fn greater_or_equal() {
+        assert_exhaustive(">=", |left, right| left >= right);
+    }
+
+    #[test]
+    fn less() {
+        assert_exhaustive("<", |left, right| left < right);
+    }
+
+    #[test]
+    fn less_or_equal() {
+        assert_exhaustive("<=", |left, right| left <= right);
+    }
+
+    #[test]
+    fn signed_greater() {
+        assert_exhaustive(">S", |left, right| signed(left) > signed(right));
+    }
+
+    #[test]
+    fn signed_greater_or_equal() {
+        assert_exhaustive(">=S", |left, right| signed(left) >= signed(right));
+    }
+
+    #[test]
+    fn signed_less() {
+        assert_exhaustive("<S", |left, right| signed(left) < signed(right));
+    }
+
+    #[test]
+    fn signed_less_or_equal() {
+        assert_exhaustive("<=S", |left, right| signed(left) <= signed(right));
+    }
+
+    #[test]
+    fn signed_less_than_negative_immediate() {
+        let machine_code_result =
+            compile_to_binary("LIH [C <S -5] TEST\nHLT\nLAB TEST\nHLT", u12::new(0));
+        assert_eq!(machine_code_result.diagnostics.len(), 0);
+        let test = machine_code_result.symbol_table.get("TEST").cloned();
+
+        for value in 0..64 {
+            let mut state = InteractiveState::new();
+            state
+                .memory
+                .store_array(0, &machine_code_result.binary)
+                .unwrap();
+            state.c = u6::new(value);
+            state.consume_until_halt().unwrap();
+            assert_eq!(
+                Some(tuple_as_u12(state.program_counter.as_tuple())) == test,
+                signed(value) < -5,
+                "[{value} <S -5]"
+            );
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use crate::{
+    compilation::{
+        compile_to_object,
+        diagnostic::DiagKind,
+        object::{link, LinkError, Object},
+    },
+    emulation::InteractiveState,
+    utils::usize_as_tuple,
+};
+use arbitrary_int::{u12, u6};
+use std::str::FromStr;
+
+const MAIN: &str = "MOV A 5\nPC DOUBLE\nLAB RETURN\nHLT";
+const LIBRARY: &str = "LAB DOUBLE\nPC .SKIP\nLAB .SKIP\nADD A A\nPC RETURN";
+
+fn object(source: &str) -> Object {
+    let object_info = compile_to_object(source);
+    assert_eq!(object_info.diagnostics.len(), 0);
+    object_info.object
+}
+
+fn run_linked(offset: u16) {
+    let binary = link(&[object(MAIN), object(LIBRARY)], u12::new(offset)).unwrap();
+
+    let mut state = InteractiveState::new();
+    state.memory.store_array(offset as usize, &binary).unwrap();
+    state.program_counter.set(usize_as_tuple(offset as usize));
+    state.consume_until_halt().unwrap();
+    assert_eq!(state.a, u6::new(10), "linked at {offset}");
+}
+
+#[test]
+fn link_at_zero() {
+    run_linked(0);
+}
+
+#[test]
+fn link_at_offset() {
+    run_linked(100);
+    run_linked(3000);
+}
+
+#[test]
+fn object_round_trip() {
+    let object = object(LIBRARY);
+    assert_eq!(object.relocations.len(), 4);
+    assert_eq!(Object::from_str(&object.to_string()).unwrap(), object);
+}
+
+/// Link `source` alone at `offset`, run it and return the state and the address of `DATA`.
+fn run_alone(source: &str, offset: u16) -> (InteractiveState, usize) {
+    let object = object(source);
+    assert_eq!(Object::from_str(&object.to_string()).unwrap(), object);
+    let data = offset as usize + object.symbols["DATA"].value() as usize;
+    let binary = link(&[object], u12::new(offset)).unwrap();
+
+    let mut state = InteractiveState::new();
+    state.memory.store_array(offset as usize, &binary).unwrap();
+    state.program_counter.set(usize_as_tuple(offset as usize));
+    state.consume_until_halt().unwrap();
+    (state, data)
+}
+
+#[test]
+fn complemented_relocations() {
+    let relocations = object("LAB DATA\nAND A DATA:1").relocations;
+    assert!(relocations.iter().all(|relocation| relocation.complement));
+
+    // `AND` and `LIH` store the NOT of their immediates, which the linker has to keep.
+    for offset in [0, 100, 3000] {
+        let (state, data) = run_alone("MOV A 0b111111\nAND A DATA:1\nHLT\nLAB DATA\nSET 0", offset);
+        assert_eq!(
+            state.a,
+            u6::new((data & 0b111111) as u8),
+            "linked at {offset}"
+        );
+
+        for condition in ["[A == DATA:0]", "[DATA:0 == A]"] {
+            let source = format!("MOV A DATA:0\nLIH {condition} DATA\nHLT\nLAB DATA\nMOV B 1\nHLT");
+            let (state, _) = run_alone(&source, offset);
+            assert_eq!(state.b, u6::new(1), "{condition} linked at {offset}");
+        }
+    }
+}
+
+#[test]
+fn undefined_private_label() {
+    // Anonymous labels are never exported, so no other object can define this one.
+    let object_info = compile_to_object("PC +\nHLT");
+    let kinds: Vec<_> = object_info
+        .diagnostics
+        .into_iter()
+        .map(|d| d.kind)
+        .collect();
+    assert_eq!(kinds, [DiagKind::UndefinedLabel]);
+}
+
+#[test]
+fn undefined_symbol() {
+    assert!(matches!(
+        link(&[object(MAIN)], u12::new(0)),
+        Err(LinkError::UndefinedSymbol(symbol)) if &*symbol == "DOUBLE"
+    ));
+}
+
+#[test]
+fn duplicate_symbol() {
+    assert!(matches!(
+        link(&[object(LIBRARY), object(LIBRARY)], u12::new(0)),
+        Err(LinkError::DuplicateSymbol(_))
+    ));
+}
+
+#[test]
+fn non_relocatable_expression() {
+    let diagnostics = compile_to_object("LAB X\nSET (X:1 + 1)").diagnostics;
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].kind, DiagKind::NonRelocatable);
+}
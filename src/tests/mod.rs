@@ -1,8 +1,18 @@
+mod arithmetic;
 mod bitwise_logic;
+mod checks;
+mod control_flow;
+mod coverage;
+mod cycles;
 mod halt_and_nop;
+mod history;
 mod lab_and_pc;
 mod lih;
+mod linking;
+mod profiling;
 mod shift_and_rotate;
+mod tracing;
+mod trap_and_interrupt;
 
 /// Quickly create compact tests!
 #[macro_export]
@@ -21,7 +31,11 @@ macro_rules! test_builder {
             let mut state = InteractiveState::new();
             let machine_code_result = compile_to_binary($source, u12::new(0));
             assert_eq!(machine_code_result.diagnostics.len(), 0);
-            state.memory.store_array(0, &machine_code_result.binary);
+            state
+                .memory
+                .store_array(0, &machine_code_result.binary)
+                .unwrap();
+            state.checks = machine_code_result.debug_info.checks.clone();
             let mutation_closure = |$mutation_state: &mut InteractiveState| $mutation_closure;
             mutation_closure(&mut state);
             let assertion_closure =
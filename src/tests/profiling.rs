@@ -0,0 +1,88 @@
+use crate::{
+    compilation::{compile_to_binary, CompileInfo},
+    emulation::{InteractiveState, Profile},
+    test_builder,
+};
+use arbitrary_int::{u12, u6};
+
+test_builder!(
+    statement_lines,
+    "# COMMENT\nNOR A 1\n\nLAB DONE\n    NOP  \nHLT",
+    |_state| {},
+    |_state, machine_code_result| {
+        let statements = &machine_code_result.debug_info.statements;
+        let lines: Vec<_> = statements.iter().map(|statement| statement.line).collect();
+        assert_eq!(lines, vec![2, 5, 6]);
+        assert_eq!(statements[1].address, u12::new(2));
+
+        let source = "# COMMENT\nNOR A 1\n\nLAB DONE\n    NOP  \nHLT";
+        assert_eq!(&source[statements[1].span.as_range()], "NOP");
+
+        let debug_info = &machine_code_result.debug_info;
+        assert_eq!(debug_info.statement_at(u12::new(1)).unwrap().line, 2);
+        assert!(debug_info.statement_at(u12::new(4)).is_none());
+    }
+);
+
+test_builder!(
+    profile_by_label,
+    "NOP\nLAB LOOP\nNOR A 1\nLAB LOOP.INNER\nNOR B 0\nHLT",
+    |state| {
+        state.profile = Some(Profile::new());
+        state.consume_until_halt().unwrap();
+    },
+    |state, machine_code_result| {
+        let profile = state.profile.as_ref().unwrap();
+        let labels: Vec<_> = profile
+            .by_label(&machine_code_result.symbol_table)
+            .into_iter()
+            .map(|(label, count)| (label.to_string(), count.executions, count.cycles))
+            .collect();
+        assert_eq!(
+            labels,
+            vec![
+                ("LOOP".to_string(), 1, 2),
+                ("LOOP.INNER".to_string(), 1, 2),
+                ("(unlabeled)".to_string(), 1, 1),
+            ]
+        );
+    }
+);
+
+test_builder!(
+    profile_by_line,
+    "NOR A A\nNOR A 1\nNOR A 1\nPC 0 1",
+    |state| {
+        state.profile = Some(Profile::new());
+        for _ in 0..7 {
+            state.consume_instruction().unwrap();
+        }
+    },
+    |state, machine_code_result| {
+        let profile = state.profile.as_ref().unwrap();
+        let lines: Vec<_> = profile
+            .by_line(&machine_code_result.debug_info)
+            .into_iter()
+            .map(|(line, count)| (line, count.executions))
+            .collect();
+        // `PC 0 1` jumps into the immediate of the first `NOR A 1`, which is still line 2
+        assert_eq!(lines, vec![(4, 2), (2, 2), (3, 2), (1, 1)]);
+    }
+);
+
+test_builder!(
+    profile_folded,
+    "LAB MAIN\nNOR A 1\nLAB .LOOP\nNOR B 1\nHLT",
+    |state| {
+        state.profile = Some(Profile::new());
+        state.consume_until_halt().unwrap();
+    },
+    |state, machine_code_result| {
+        let folded = state.profile.as_ref().unwrap().folded(
+            &machine_code_result.symbol_table,
+            &machine_code_result.debug_info,
+        );
+        assert_eq!(folded, "MAIN;.LOOP;line 4 2\nMAIN;line 2 2\n");
+        assert_eq!(state.program_counter.as_tuple(), (u6::new(0), u6::new(4)));
+    }
+);
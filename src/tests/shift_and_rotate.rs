@@ -11,7 +11,7 @@ test_builder!(
     "ROL A\nHLT",
     |state| {
         state.a = u6::new(0b101101);
-        state.consume_until_halt();
+        state.consume_until_halt().unwrap();
     },
     |state, _machine_code_result| {
         assert_eq!(state.c, u6::new(0b011011));
@@ -23,7 +23,7 @@ test_builder!(
     "ROL B\nHLT",
     |state| {
         state.b = u6::new(0b101101);
-        state.consume_until_halt();
+        state.consume_until_halt().unwrap();
     },
     |state, _machine_code_result| {
         assert_eq!(state.c, u6::new(0b011011));
@@ -35,7 +35,7 @@ test_builder!(
     "ROL C\nHLT",
     |state| {
         state.c = u6::new(0b101101);
-        state.consume_until_halt();
+        state.consume_until_halt().unwrap();
     },
     |state, _machine_code_result| {
         assert_eq!(state.c, u6::new(0b011011));
@@ -48,7 +48,7 @@ test_builder!(
     "ROR A\nHLT",
     |state| {
         state.a = u6::new(0b101101);
-        state.consume_until_halt();
+        state.consume_until_halt().unwrap();
     },
     |state, _machine_code_result| {
         assert_eq!(state.c, u6::new(0b110110));
@@ -60,7 +60,7 @@ test_builder!(
     "ROR B\nHLT",
     |state| {
         state.b = u6::new(0b101101);
-        state.consume_until_halt();
+        state.consume_until_halt().unwrap();
     },
     |state, _machine_code_result| {
         assert_eq!(state.c, u6::new(0b110110));
@@ -72,7 +72,7 @@ test_builder!(
     "ROR C\nHLT",
     |state| {
         state.c = u6::new(0b101101);
-        state.consume_until_halt();
+        state.consume_until_halt().unwrap();
     },
     |state, _machine_code_result| {
         assert_eq!(state.c, u6::new(0b110110));
@@ -85,7 +85,7 @@ test_builder!(
     "SHL A\nHLT",
     |state| {
         state.a = u6::new(0b101101);
-        state.consume_until_halt();
+        state.consume_until_halt().unwrap();
     },
     |state, _machine_code_result| {
         assert_eq!(state.c, u6::new(0b011010));
@@ -97,7 +97,7 @@ test_builder!(
     "SHL B\nHLT",
     |state| {
         state.b = u6::new(0b101101);
-        state.consume_until_halt();
+        state.consume_until_halt().unwrap();
     },
     |state, _machine_code_result| {
         assert_eq!(state.c, u6::new(0b011010));
@@ -109,7 +109,7 @@ test_builder!(
     "SHL C\nHLT",
     |state| {
         state.c = u6::new(0b101101);
-        state.consume_until_halt();
+        state.consume_until_halt().unwrap();
     },
     |state, _machine_code_result| {
         assert_eq!(state.c, u6::new(0b011010));
@@ -122,7 +122,7 @@ test_builder!(
     "SHR A\nHLT",
     |state| {
         state.a = u6::new(0b101101);
-        state.consume_until_halt();
+        state.consume_until_halt().unwrap();
     },
     |state, _machine_code_result| {
         assert_eq!(state.c, u6::new(0b010110));
@@ -134,7 +134,7 @@ test_builder!(
     "SHR B\nHLT",
     |state| {
         state.b = u6::new(0b101101);
-        state.consume_until_halt();
+        state.consume_until_halt().unwrap();
     },
     |state, _machine_code_result| {
         assert_eq!(state.c, u6::new(0b010110));
@@ -146,7 +146,7 @@ test_builder!(
     "SHR C\nHLT",
     |state| {
         state.c = u6::new(0b101101);
-        state.consume_until_halt();
+        state.consume_until_halt().unwrap();
     },
     |state, _machine_code_result| {
         assert_eq!(state.c, u6::new(0b010110));
@@ -0,0 +1,74 @@
+use crate::{
+    compilation::{compile_to_binary, CompileInfo},
+    emulation::{InteractiveState, RomPolicy, Trace},
+    test_builder,
+};
+use arbitrary_int::{u12, u6};
+
+test_builder!(
+    trace_registers_and_memory,
+    "NOR C 0\nSTO 0 10\nHLT",
+    |state| {
+        state.trace = Some(Trace::new(16));
+        state.consume_until_halt().unwrap();
+    },
+    |state, _machine_code_result| {
+        let entries: Vec<_> = state.trace.as_ref().unwrap().last(16).collect();
+        assert_eq!(entries.len(), 2);
+
+        assert_eq!(entries[0].program_counter, (u6::new(0), u6::new(0)));
+        assert_eq!(entries[0].registers, vec![('C', u6::new(0), u6::new(63))]);
+        assert_eq!(entries[0].write, None);
+
+        let write = entries[1].write.unwrap();
+        assert_eq!(write.address, (u6::new(0), u6::new(10)));
+        assert_eq!((write.old, write.new), (Some(u6::new(0)), u6::new(63)));
+        assert_eq!(
+            entries[1].to_string(),
+            "002: STO 0 10         (000000, 001010) [00A]: 000000 -> 111111"
+        );
+    }
+);
+
+test_builder!(
+    trace_ring_buffer,
+    "NOP\nNOP\nNOR A A\nHLT",
+    |state| {
+        state.trace = Some(Trace::new(2));
+        state.consume_until_halt().unwrap();
+    },
+    |state, _machine_code_result| {
+        let addresses: Vec<_> = state
+            .trace
+            .as_ref()
+            .unwrap()
+            .last(5)
+            .map(|entry| entry.program_counter.1.value())
+            .collect();
+        assert_eq!(addresses, vec![1, 2]);
+    }
+);
+
+test_builder!(
+    trace_write_to_unmapped_rom,
+    "MOV C 5\nSTO 0x3D 0x00\nHLT",
+    |state| {
+        state.memory.rom_policy = RomPolicy::AllowAsRam;
+        state.trace = Some(Trace::new(16));
+        state.consume_until_halt().unwrap();
+    },
+    |state, _machine_code_result| {
+        let entries: Vec<_> = state.trace.as_ref().unwrap().last(16).collect();
+        let store = entries.last().unwrap();
+        let write = store.write.unwrap();
+        assert_eq!((write.old, write.new), (None, u6::new(5)));
+        assert_eq!(
+            store.to_string(),
+            "005: STO 61 0         (111101, 000000) [F40]: ------ -> 000101"
+        );
+        assert_eq!(
+            state.memory.read((u6::new(0x3D), u6::new(0x00))),
+            Ok(u6::new(5))
+        );
+    }
+);
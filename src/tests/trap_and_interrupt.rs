@@ -0,0 +1,75 @@
+use crate::{
+    compilation::{compile_to_binary, CompileInfo},
+    emulation::{ExecutionError, InteractiveState},
+    test_builder,
+    utils::usize_as_tuple,
+};
+use arbitrary_int::{u12, u6};
+
+test_builder!(
+    trap_calls_handler,
+    "MOV A 20\nTRP\nMOV B C\nHLT",
+    |state| {
+        state.trap_handler = Some(Box::new(|state| state.c = state.a + u6::new(1)));
+        state.consume_until_halt().unwrap();
+    },
+    |state, _machine_code_result| {
+        assert_eq!((state.a, state.b), (u6::new(20), u6::new(21)));
+    }
+);
+
+test_builder!(
+    trap_without_handler,
+    "TRP\nHLT",
+    |state| {
+        state.consume_until_halt().unwrap();
+    },
+    |state, _machine_code_result| {
+        assert_eq!(state.program_counter.as_tuple(), (u6::new(0), u6::new(1)));
+    }
+);
+
+/// Run `source` until `steps` instructions have executed, raise an interrupt to `HANDLER`, then
+/// run until halt.
+fn run_with_interrupt(source: &str, steps: usize) -> InteractiveState {
+    let machine_code_result = compile_to_binary(source, u12::new(0));
+    assert_eq!(machine_code_result.diagnostics.len(), 0);
+    let handler = machine_code_result.symbol_table["HANDLER"];
+
+    let mut state = InteractiveState::new();
+    state
+        .memory
+        .store_array(0, &machine_code_result.binary)
+        .unwrap();
+    for _ in 0..steps {
+        state.consume_instruction().unwrap();
+    }
+    state.interrupt(usize_as_tuple(handler.value() as usize));
+    state.consume_until_halt().unwrap();
+    state
+}
+
+#[test]
+fn interrupt_return() {
+    let state = run_with_interrupt("MOV A 1\nMOV A 2\nHLT\nLAB HANDLER\nMOV B 3\nIRT", 1);
+    assert_eq!((state.a, state.b), (u6::new(2), u6::new(3)));
+    assert_eq!(state.interrupt_return, None);
+}
+
+#[test]
+fn interrupt_resumes_after_halt() {
+    let state = run_with_interrupt("HLT\nMOV A 1\nHLT\nLAB HANDLER\nMOV B 3\nIRT", 0);
+    assert_eq!((state.a, state.b), (u6::new(1), u6::new(3)));
+}
+
+test_builder!(
+    return_without_interrupt,
+    "IRT",
+    |state| {
+        assert_eq!(
+            state.consume_instruction(),
+            Err(ExecutionError::ReturnWithoutInterrupt)
+        );
+    },
+    |_state, _machine_code_result| {}
+);
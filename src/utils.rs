@@ -4,6 +4,14 @@ pub fn tuple_as_usize(tuple: (u6, u6)) -> usize {
     ((u16::from(tuple.0) << 6) | u16::from(tuple.1)) as usize
 }
 
+pub fn usize_as_tuple(value: usize) -> (u6, u6) {
+    (
+        u6::new((value >> 6) as u8 & 0b111111),
+        u6::new(value as u8 & 0b111111),
+    )
+}
+
+#[allow(dead_code)]
 pub fn tuple_as_u12(tuple: (u6, u6)) -> u12 {
     u12::new((u16::from(tuple.0) << 6) | u16::from(tuple.1))
 }